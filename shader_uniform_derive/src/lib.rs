@@ -0,0 +1,454 @@
+//! `#[derive(ShaderUniform)]`, see `lib::uniforms::WgslUniform`, `#[derive(UniformControls)]`, see
+//! `lib::controls::UniformControls`, and `#[derive(Interpolate)]`, see
+//! `lib::interpolation::Interpolate`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parenthesized, parse_macro_input, Data, DeriveInput, Expr, ExprLit, Field, Fields, Lit, LitStr, Type};
+
+/// Implements `WgslUniform` for a `#[repr(C)]` struct with named fields and checks at compile time
+/// that its layout matches the WGSL one.
+///
+/// Fields whose name starts with `_` are Rust only padding. The others map to WGSL types:
+///
+/// | Rust                      | WGSL                |
+/// |---------------------------|---------------------|
+/// | `f32`, `i32`, `u32`       | `f32`, `i32`, `u32` |
+/// | `[f32; 2]` to `[f32; 4]`  | `vec2<f32>` to `vec4<f32>` |
+/// | `[u32; 2]`, `[u32; 4]`    | `vec2<u32>`, `vec4<u32>` |
+/// | `[[f32; 4]; 4]`           | `mat4x4<f32>`       |
+/// | `[[f32; 4]; N]`, ...      | `array<vec4<f32>, N>`, ... |
+#[proc_macro_derive(ShaderUniform)]
+pub fn derive_shader_uniform(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(input.generics.span(), "`ShaderUniform` can't be derived for generic structs"));
+    }
+    let repr_c = input.attrs.iter().any(|attr| {
+        attr.path().is_ident("repr")
+            && attr.parse_args::<syn::Ident>().is_ok_and(|repr| repr == "C")
+    });
+    if !repr_c {
+        return Err(syn::Error::new(name.span(), "`ShaderUniform` needs `#[repr(C)]`"));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new(name.span(), "`ShaderUniform` needs named fields")),
+        },
+        _ => return Err(syn::Error::new(name.span(), "`ShaderUniform` can only be derived for structs")),
+    };
+
+    let mut field_entries = Vec::new();
+    let mut assertions = Vec::new();
+    let wgsl_fields = fields
+        .iter()
+        .filter(|field| !field.ident.as_ref().unwrap().to_string().starts_with('_'));
+    for (index, field) in wgsl_fields.enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let field_name = ident.to_string();
+        let ty = wgsl_type(&field.ty)?;
+        field_entries.push(quote! {
+            ::lib::uniforms::UniformField::new(#field_name, ::std::mem::offset_of!(#name, #ident), #ty)
+        });
+
+        let stride_message = format!(
+            "`{}` of `{}` is an array with a stride that is not a multiple of 16, use vec4 elements",
+            field_name, name
+        );
+        let offset_message = format!(
+            "`{}` of `{}` is not at the offset WGSL expects, check the padding before it",
+            field_name, name
+        );
+        assertions.push(quote_spanned! {field.span()=>
+            assert!(::lib::uniforms::has_uniform_stride(&#ty), #stride_message);
+            assert!(
+                ::std::mem::offset_of!(#name, #ident) == ::lib::uniforms::wgsl_offset(FIELDS, #index),
+                #offset_message
+            );
+        });
+    }
+
+    let size_message = format!(
+        "`{}` doesn't have the size of its WGSL struct, check the trailing padding",
+        name
+    );
+    let wgsl_name = name.to_string();
+    Ok(quote! {
+        impl ::lib::uniforms::WgslUniform for #name {
+            const FIELDS: &'static [::lib::uniforms::UniformField] = &[#(#field_entries),*];
+
+            fn wgsl_name() -> &'static str {
+                #wgsl_name
+            }
+        }
+
+        const _: () = {
+            const FIELDS: &[::lib::uniforms::UniformField] = <#name as ::lib::uniforms::WgslUniform>::FIELDS;
+            #(#assertions)*
+            assert!(
+                ::std::mem::size_of::<#name>() == ::lib::uniforms::wgsl_size(FIELDS),
+                #size_message
+            );
+        };
+    })
+}
+
+fn wgsl_type(ty: &Type) -> syn::Result<TokenStream2> {
+    let unsupported = || {
+        syn::Error::new(
+            ty.span(),
+            "unsupported uniform field type, expected a scalar, a vector (`[f32; 2]`), `[[f32; 4]; 4]` or an array of vectors",
+        )
+    };
+    match ty {
+        Type::Path(path) => {
+            let ident = path.path.get_ident().ok_or_else(unsupported)?;
+            match ident.to_string().as_str() {
+                "f32" => Ok(quote!(::lib::uniforms::WgslType::F32)),
+                "i32" => Ok(quote!(::lib::uniforms::WgslType::I32)),
+                "u32" => Ok(quote!(::lib::uniforms::WgslType::U32)),
+                _ => Err(unsupported()),
+            }
+        }
+        Type::Array(array) => {
+            let element = scalar_name(&array.elem);
+            match (element.as_deref(), literal_len(&array.len)) {
+                (Some("f32"), Some(2)) => Ok(quote!(::lib::uniforms::WgslType::Vec2F32)),
+                (Some("f32"), Some(3)) => Ok(quote!(::lib::uniforms::WgslType::Vec3F32)),
+                (Some("f32"), Some(4)) => Ok(quote!(::lib::uniforms::WgslType::Vec4F32)),
+                (Some("u32"), Some(2)) => Ok(quote!(::lib::uniforms::WgslType::Vec2U32)),
+                (Some("u32"), Some(4)) => Ok(quote!(::lib::uniforms::WgslType::Vec4U32)),
+                (Some(_), _) => Err(unsupported()),
+                (None, len) => {
+                    let element = wgsl_type(&array.elem)?;
+                    let is_vec4 = matches!(&*array.elem, Type::Array(inner)
+                        if scalar_name(&inner.elem).as_deref() == Some("f32") && literal_len(&inner.len) == Some(4));
+                    if is_vec4 && len == Some(4) {
+                        return Ok(quote!(::lib::uniforms::WgslType::Mat4x4F32));
+                    }
+                    let len = &array.len;
+                    Ok(quote!(::lib::uniforms::WgslType::Array(&#element, #len)))
+                }
+            }
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn scalar_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        _ => None,
+    }
+}
+
+fn literal_len(len: &Expr) -> Option<usize> {
+    match len {
+        Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => int.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+/// Implements `UniformControls` for a struct with named fields, with a widget for each field that
+/// has a `#[control(...)]` attribute:
+///
+/// | Attribute           | Field types                     | Widget            |
+/// |---------------------|---------------------------------|-------------------|
+/// | `slider(min..=max)` | `f32`, `i32`, `u32`             | `egui::Slider`    |
+/// | `drag`              | `f32`, `i32`, `u32`, `[f32; 2]` | `egui::DragValue` |
+/// | `color`             | `[f32; 3]`, `[f32; 4]`          | color picker      |
+/// | `toggle`            | `bool`, `u32`                   | checkbox          |
+///
+/// Sliders can also be `logarithmic`, drags take a `speed = 0.1`, and every widget a
+/// `label = "..."` instead of the field name.
+#[proc_macro_derive(UniformControls, attributes(control))]
+pub fn derive_uniform_controls(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_controls(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+enum Widget {
+    Slider(Expr),
+    Drag,
+    Color,
+    Toggle,
+}
+
+struct Control {
+    widget: Widget,
+    label: Option<LitStr>,
+    logarithmic: bool,
+    speed: Option<Expr>,
+}
+
+fn expand_controls(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new(name.span(), "`UniformControls` needs named fields")),
+        },
+        _ => return Err(syn::Error::new(name.span(), "`UniformControls` can only be derived for structs")),
+    };
+
+    let mut widgets = Vec::new();
+    for field in fields {
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("control")) {
+            let control = parse_control(attr)?;
+            widgets.push(control_widget(field, &control)?);
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lib::controls::UniformControls for #name #ty_generics #where_clause {
+            fn controls(&mut self, ui: &mut ::lib::controls::egui::Ui) -> bool {
+                let mut changed = false;
+                #(changed |= #widgets;)*
+                changed
+            }
+        }
+    })
+}
+
+fn parse_control(attr: &syn::Attribute) -> syn::Result<Control> {
+    let mut widget = None;
+    let mut label = None;
+    let mut logarithmic = false;
+    let mut speed = None;
+    attr.parse_nested_meta(|meta| {
+        let ident = meta.path.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+        let kind = match ident.as_str() {
+            "slider" => {
+                let content;
+                parenthesized!(content in meta.input);
+                Some(Widget::Slider(content.parse()?))
+            }
+            "drag" => Some(Widget::Drag),
+            "color" => Some(Widget::Color),
+            "toggle" => Some(Widget::Toggle),
+            "label" => {
+                label = Some(meta.value()?.parse()?);
+                None
+            }
+            "logarithmic" => {
+                logarithmic = true;
+                None
+            }
+            "speed" => {
+                speed = Some(meta.value()?.parse()?);
+                None
+            }
+            _ => return Err(meta.error("unknown control, expected `slider(min..=max)`, `drag`, `color` or `toggle`")),
+        };
+        if let Some(kind) = kind {
+            if widget.is_some() {
+                return Err(meta.error("a field can only have one widget"));
+            }
+            widget = Some(kind);
+        }
+        Ok(())
+    })?;
+    let widget = widget.ok_or_else(|| {
+        syn::Error::new(attr.span(), "missing widget, expected `slider(min..=max)`, `drag`, `color` or `toggle`")
+    })?;
+    if logarithmic && !matches!(widget, Widget::Slider(_)) {
+        return Err(syn::Error::new(attr.span(), "only sliders can be `logarithmic`"));
+    }
+    if speed.is_some() && !matches!(widget, Widget::Drag) {
+        return Err(syn::Error::new(attr.span(), "only drags have a `speed`"));
+    }
+    Ok(Control {
+        widget,
+        label,
+        logarithmic,
+        speed,
+    })
+}
+
+fn control_widget(field: &Field, control: &Control) -> syn::Result<TokenStream2> {
+    let ident = field.ident.as_ref().unwrap();
+    let label = match &control.label {
+        Some(label) => label.value(),
+        None => sentence_case(&ident.to_string()),
+    };
+    let ty = type_name(&field.ty);
+    let mismatch = |expected: &str| {
+        syn::Error::new(field.ty.span(), format!("this control needs a field of type {}", expected))
+    };
+    let span = field.span();
+    match &control.widget {
+        Widget::Slider(range) => match ty.as_deref() {
+            Some("f32" | "i32" | "u32") => {
+                let logarithmic = control.logarithmic;
+                Ok(quote_spanned! {span=>
+                    ::lib::controls::slider(ui, #label, &mut self.#ident, #range, #logarithmic)
+                })
+            }
+            _ => Err(mismatch("`f32`, `i32` or `u32`")),
+        },
+        Widget::Drag => {
+            let speed = match &control.speed {
+                Some(speed) => quote!(#speed),
+                None => quote!(1.0),
+            };
+            match ty.as_deref() {
+                Some("f32" | "i32" | "u32") => Ok(quote_spanned! {span=>
+                    ::lib::controls::drag(ui, #label, &mut self.#ident, #speed)
+                }),
+                Some("[f32; 2]") => Ok(quote_spanned! {span=>
+                    ::lib::controls::drag_vec2(ui, #label, &mut self.#ident, #speed)
+                }),
+                _ => Err(mismatch("`f32`, `i32`, `u32` or `[f32; 2]`")),
+            }
+        }
+        Widget::Color => match ty.as_deref() {
+            Some("[f32; 3]") => Ok(quote_spanned! {span=>
+                ::lib::controls::color_rgb(ui, #label, &mut self.#ident)
+            }),
+            Some("[f32; 4]") => Ok(quote_spanned! {span=>
+                ::lib::controls::color_rgba(ui, #label, &mut self.#ident)
+            }),
+            _ => Err(mismatch("`[f32; 3]` or `[f32; 4]`")),
+        },
+        Widget::Toggle => match ty.as_deref() {
+            Some("bool") => Ok(quote_spanned! {span=>
+                ::lib::controls::toggle(ui, #label, &mut self.#ident)
+            }),
+            Some("u32") => Ok(quote_spanned! {span=>
+                ::lib::controls::toggle_u32(ui, #label, &mut self.#ident)
+            }),
+            _ => Err(mismatch("`bool` or `u32`")),
+        },
+    }
+}
+
+/// `f32`, `[f32; 2]`... or `None` for anything else.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Array(array) => {
+            let element = scalar_name(&array.elem)?;
+            let len = literal_len(&array.len)?;
+            Some(format!("[{}; {}]", element, len))
+        }
+        _ => scalar_name(ty),
+    }
+}
+
+/// `color_blend` becomes `Color blend`.
+fn sentence_case(name: &str) -> String {
+    let words = name.split('_').filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+/// Implements `Interpolate` for a struct with named fields, field by field.
+///
+/// Fields use their own `Interpolate` implementation unless they have one of these attributes:
+///
+/// | Attribute                  | Field types            | Blend                       |
+/// |----------------------------|------------------------|-----------------------------|
+/// | `#[interpolate(color)]`    | `[f32; 3]`, `[f32; 4]` | in Oklab, from linear RGB   |
+/// | `#[interpolate(degrees)]`  | `f32`                  | along the shortest arc      |
+/// | `#[interpolate(radians)]`  | `f32`                  | along the shortest arc      |
+/// | `#[interpolate(step)]`     | any `Clone`            | switches halfway            |
+/// | `#[interpolate(skip)]`     | any `Clone`            | keeps the value of `self`   |
+#[proc_macro_derive(Interpolate, attributes(interpolate))]
+pub fn derive_interpolate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_interpolate(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_interpolate(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new(name.span(), "`Interpolate` needs named fields")),
+        },
+        _ => return Err(syn::Error::new(name.span(), "`Interpolate` can only be derived for structs")),
+    };
+
+    let mut blended = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let mut kind = None;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("interpolate")) {
+            attr.parse_nested_meta(|meta| {
+                if kind.is_some() {
+                    return Err(meta.error("a field can only be interpolated one way"));
+                }
+                let ident = meta.path.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+                match ident.as_str() {
+                    "color" | "degrees" | "radians" | "step" | "skip" => {
+                        kind = Some(ident);
+                        Ok(())
+                    }
+                    _ => Err(meta.error("expected `color`, `degrees`, `radians`, `step` or `skip`")),
+                }
+            })?;
+        }
+        let ty = type_name(&field.ty);
+        let mismatch = |expected: &str| {
+            syn::Error::new(field.ty.span(), format!("this interpolation needs a field of type {}", expected))
+        };
+        let span = field.span();
+        let value = match (kind.as_deref(), ty.as_deref()) {
+            (None, _) => quote_spanned! {span=>
+                ::lib::interpolation::Interpolate::interpolate(&self.#ident, &other.#ident, t)
+            },
+            (Some("color"), Some("[f32; 3]")) => quote_spanned! {span=>
+                ::lib::interpolation::lerp_color(self.#ident, other.#ident, t)
+            },
+            (Some("color"), Some("[f32; 4]")) => quote_spanned! {span=>
+                ::lib::interpolation::lerp_color_alpha(self.#ident, other.#ident, t)
+            },
+            (Some("color"), _) => return Err(mismatch("`[f32; 3]` or `[f32; 4]`")),
+            (Some("degrees"), Some("f32")) => quote_spanned! {span=>
+                ::lib::interpolation::lerp_angle_degrees(self.#ident, other.#ident, t)
+            },
+            (Some("radians"), Some("f32")) => quote_spanned! {span=>
+                ::lib::interpolation::lerp_angle(self.#ident, other.#ident, t)
+            },
+            (Some("degrees" | "radians"), _) => return Err(mismatch("`f32`")),
+            (Some("step"), _) => quote_spanned! {span=>
+                ::lib::interpolation::step(&self.#ident, &other.#ident, t)
+            },
+            (Some(_), _) => quote_spanned! {span=>
+                ::std::clone::Clone::clone(&self.#ident)
+            },
+        };
+        blended.push(quote!(#ident: #value));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lib::interpolation::Interpolate for #name #ty_generics #where_clause {
+            fn interpolate(&self, other: &Self, t: f32) -> Self {
+                #name {
+                    #(#blended),*
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,536 @@
+//! Runs the effects we tune interactively in the examples over folders of images, headlessly.
+//!
+//! ```text
+//! batch_process --effect dog --accentuate 8 --output out/ assets/*.jpg
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nannou::image;
+use nannou::image::{DynamicImage, GenericImageView};
+use nannou::wgpu;
+
+use lib::canvas::tiled::{render_tiled, DEFAULT_TILE_SIZE};
+use lib::compute_kernel::create_storage_texture;
+use lib::compute_kernel::dog::{Dog, DogUniforms};
+use lib::compute_kernel::flow_dog::{FlowDog, FlowDogParams};
+use lib::compute_kernel::gaussian_blur::GaussianBlur;
+use lib::compute_kernel::xdog::{Xdog, XdogParams};
+use lib::preprocessor::Preprocessor;
+use lib::shader_processing::convolution::ConvolutionPreset;
+use lib::shader_processing::model::ConvolutionUniform;
+use lib::shader_processing::offscreen::{encode_srgb, HeadlessGpu, save_png};
+
+const USAGE: &str = "\
+Usage: batch_process --effect <name> --output <dir> [options] <inputs>...
+
+Inputs are image paths, `*` and `?` wildcards are expanded within the file name.
+
+Effects:
+    dog            Difference of gaussians edge map (examples/shaders/cs.wgsl)
+    convolution    Convolution fragment shader (examples/shaders/fs.wgsl)
+    blur           Separable gaussian blur
+    xdog           Extended difference of gaussians ink effect
+    fdog           Flow-based XDoG, coherent lines following the edges
+
+Options:
+    --effect <name>        Effect to apply
+    --output <dir>         Directory where the resulting PNG files are written
+    --accentuate <value>   Strength of the DoG edges [default: 1]
+    --time <seconds>       Value of the `time` uniform [default: 0]
+    --seed <integer>       Seed of the DoG grain noise [default: 0]
+    --grain <amount>       Strength of the DoG grain noise [default: 0]
+    --sigma <pixels>       Standard deviation of the gaussian blur [default: 2, xdog: 1.4, fdog: 1]
+    --k <ratio>            (F)XDoG ratio between both blurs [default: 1.6]
+    --tau <weight>         (F)XDoG sharpening weight [default: 20]
+    --epsilon <value>      (F)XDoG threshold [default: 0.6]
+    --phi <value>          (F)XDoG threshold steepness [default: 10]
+    --color-blend <value>  (F)XDoG blend with the source colors [default: 0]
+    --tensor-sigma <px>    FDoG smoothing of the edge flow [default: 2]
+    --sigma-m <pixels>     FDoG smoothing along the edges [default: 3]
+    --kernel <name>        Convolution kernel: identity, sharpen, emboss, box_blur or
+                           edge_detect [default: identity]
+    --size <W>x<H>         Convolution output size, rendered in tiles so it can exceed the
+                           texture limits [default: input size]
+";
+
+enum Effect {
+    Dog,
+    Convolution,
+    Blur,
+    Xdog,
+    FlowDog,
+}
+
+/// What the command line asks for.
+enum Command {
+    Run(Options),
+    Help,
+}
+
+struct Options {
+    effect: Effect,
+    output: PathBuf,
+    accentuate: f32,
+    time: f32,
+    seed: u32,
+    grain: f32,
+    sigma: Option<f32>,
+    xdog: XdogParams,
+    flow_dog: FlowDogParams,
+    kernel: ConvolutionPreset,
+    size: Option<[u32; 2]>,
+    inputs: Vec<PathBuf>,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(err) = run(&options) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let gpu = HeadlessGpu::new()?;
+    println!("Using adapter: {} ({:?})", gpu.adapter_info.name, gpu.adapter_info.backend);
+    let outputs = output_paths(&options.inputs, &options.output)?;
+    fs::create_dir_all(&options.output)?;
+
+    for (input, output) in options.inputs.iter().zip(&outputs) {
+        let image = image::open(input).map_err(|err| format!("{}: {}", input.display(), err))?;
+        let result = match options.effect {
+            Effect::Dog => apply_dog(&gpu, &image, options)?,
+            Effect::Convolution => apply_convolution(&gpu, &image, options)?,
+            Effect::Blur => apply_blur(&gpu, &image, options)?,
+            Effect::Xdog => apply_xdog(&gpu, &image, options)?,
+            Effect::FlowDog => apply_flow_dog(&gpu, &image, options)?,
+        };
+        save_png(&result, output)?;
+        println!("{} -> {}", input.display(), output.display());
+    }
+    Ok(())
+}
+
+/// The PNG written for each input, named after it in `output`. Inputs that only differ by their
+/// extension or directory (e.g. `a.jpg` and `a.png`) would overwrite each other and are rejected.
+fn output_paths(inputs: &[PathBuf], output: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut sources: HashMap<PathBuf, &Path> = HashMap::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let stem = input.file_stem().unwrap_or_default();
+            let path = output.join(format!("{}.png", stem.to_string_lossy()));
+            match sources.insert(path.clone(), input) {
+                Some(other) => Err(format!(
+                    "{} and {} would both be written to {}",
+                    other.display(),
+                    input.display(),
+                    path.display()
+                )),
+                None => Ok(path),
+            }
+        })
+        .collect()
+}
+
+/// Uploads the image, runs the kernel that `build` creates from it into a storage texture of the
+/// same size and reads the result back. Fails if `build` does.
+fn run_kernel<K>(
+    gpu: &HeadlessGpu,
+    image: &DynamicImage,
+    label: &str,
+    build: impl FnOnce(&wgpu::Device, &wgpu::Texture, &wgpu::Texture) -> Result<K, Box<dyn Error>>,
+    dispatch: impl FnOnce(&K, &mut wgpu::CommandEncoder),
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let texture = wgpu::Texture::from_image((&gpu.device, &gpu.queue), image);
+    let storage_texture = create_storage_texture(&gpu.device, texture.size());
+    let kernel = build(&gpu.device, &texture, &storage_texture)?;
+
+    let desc = wgpu::CommandEncoderDescriptor { label: Some(label) };
+    let mut encoder = gpu.device.create_command_encoder(&desc);
+    dispatch(&kernel, &mut encoder);
+    gpu.queue.submit(Some(encoder.finish()));
+
+    let mut result = gpu.read_texture(&storage_texture)?;
+    encode_srgb(&mut result);
+    Ok(DynamicImage::ImageRgba8(result))
+}
+
+fn apply_dog(gpu: &HeadlessGpu, image: &DynamicImage, options: &Options) -> Result<DynamicImage, Box<dyn Error>> {
+    let uniforms = DogUniforms {
+        color: [1.0; 3],
+        accentuate: options.accentuate,
+        invert: 0,
+        time: options.time,
+        seed: options.seed,
+        grain: options.grain,
+    };
+    let cs = include_str!("../examples/shaders/cs.wgsl");
+    let build = |device: &wgpu::Device, texture: &wgpu::Texture, output: &wgpu::Texture| {
+        Ok(Dog::new(device, cs, texture, output, uniforms)?)
+    };
+    run_kernel(gpu, image, "batch-dog", build, Dog::dispatch)
+}
+
+fn apply_blur(gpu: &HeadlessGpu, image: &DynamicImage, options: &Options) -> Result<DynamicImage, Box<dyn Error>> {
+    let sigma = options.sigma.unwrap_or(2.0);
+    let build = |device: &wgpu::Device, texture: &wgpu::Texture, output: &wgpu::Texture| {
+        Ok(GaussianBlur::new(device, texture, output, sigma))
+    };
+    run_kernel(gpu, image, "batch-blur", build, GaussianBlur::dispatch)
+}
+
+fn apply_xdog(gpu: &HeadlessGpu, image: &DynamicImage, options: &Options) -> Result<DynamicImage, Box<dyn Error>> {
+    let params = XdogParams {
+        sigma: options.sigma.unwrap_or(options.xdog.sigma),
+        ..options.xdog
+    };
+    let build = |device: &wgpu::Device, texture: &wgpu::Texture, output: &wgpu::Texture| {
+        Ok(Xdog::new(device, texture, output, params))
+    };
+    run_kernel(gpu, image, "batch-xdog", build, Xdog::dispatch)
+}
+
+fn apply_flow_dog(gpu: &HeadlessGpu, image: &DynamicImage, options: &Options) -> Result<DynamicImage, Box<dyn Error>> {
+    let xdog = &options.xdog;
+    let params = FlowDogParams {
+        sigma_e: options.sigma.unwrap_or(options.flow_dog.sigma_e),
+        k: xdog.k,
+        tau: xdog.tau,
+        epsilon: xdog.epsilon,
+        phi: xdog.phi,
+        color_blend: xdog.color_blend,
+        ..options.flow_dog
+    };
+    let build = |device: &wgpu::Device, texture: &wgpu::Texture, output: &wgpu::Texture| {
+        Ok(FlowDog::new(device, texture, output, params))
+    };
+    run_kernel(gpu, image, "batch-flow-dog", build, FlowDog::dispatch)
+}
+
+fn apply_convolution(gpu: &HeadlessGpu, image: &DynamicImage, options: &Options) -> Result<DynamicImage, Box<dyn Error>> {
+    let fs = Preprocessor::new()
+        .with_uniform::<ConvolutionUniform>()
+        .process("fs.wgsl", include_str!("../examples/shaders/fs.wgsl"))?;
+    let mut shader_model = gpu.init_shader(image, fs.descriptor("fs.wgsl"), &options.kernel.kernel());
+    let (width, height) = image.dimensions();
+    match options.size {
+        Some(size) => {
+            let result = render_tiled(&gpu.device, &gpu.queue, &mut shader_model, size, DEFAULT_TILE_SIZE)?;
+            Ok(DynamicImage::ImageRgba8(result))
+        }
+        None => Ok(gpu.render(&shader_model, [width, height])?),
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut effect = None;
+    let mut output = None;
+    let mut accentuate = 1.0;
+    let mut time = 0.0;
+    let mut seed = 0;
+    let mut grain = 0.0;
+    let mut sigma = None;
+    let mut xdog = XdogParams::default();
+    let mut flow_dog = FlowDogParams::default();
+    let mut kernel = ConvolutionPreset::Identity;
+    let mut size = None;
+    let mut inputs = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--effect" => {
+                effect = Some(match value_of(&arg, args.next())?.as_str() {
+                    "dog" => Effect::Dog,
+                    "convolution" => Effect::Convolution,
+                    "blur" => Effect::Blur,
+                    "xdog" => Effect::Xdog,
+                    "fdog" => Effect::FlowDog,
+                    other => return Err(format!("unknown effect `{}`", other)),
+                });
+            }
+            "--output" => output = Some(PathBuf::from(value_of(&arg, args.next())?)),
+            "--accentuate" => accentuate = parse_number(&arg, args.next())?,
+            "--time" => time = parse_number(&arg, args.next())?,
+            "--seed" => seed = parse_number(&arg, args.next())?,
+            "--grain" => grain = parse_number(&arg, args.next())?,
+            "--sigma" => sigma = Some(parse_number(&arg, args.next())?),
+            "--k" => xdog.k = parse_number(&arg, args.next())?,
+            "--tau" => xdog.tau = parse_number(&arg, args.next())?,
+            "--epsilon" => xdog.epsilon = parse_number(&arg, args.next())?,
+            "--phi" => xdog.phi = parse_number(&arg, args.next())?,
+            "--color-blend" => xdog.color_blend = parse_number(&arg, args.next())?,
+            "--tensor-sigma" => flow_dog.tensor_sigma = parse_number(&arg, args.next())?,
+            "--sigma-m" => flow_dog.sigma_m = parse_number(&arg, args.next())?,
+            "--kernel" => {
+                let name = value_of(&arg, args.next())?;
+                kernel = ConvolutionPreset::from_name(&name)
+                    .ok_or_else(|| format!("unknown kernel `{}`", name))?;
+            }
+            "--size" => size = Some(parse_size(&arg, args.next())?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+            pattern => inputs.extend(expand_pattern(pattern)?),
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err("no input images".to_string());
+    }
+    Ok(Command::Run(Options {
+        effect: effect.ok_or("missing --effect")?,
+        output: output.ok_or("missing --output")?,
+        accentuate,
+        time,
+        seed,
+        grain,
+        sigma,
+        xdog,
+        flow_dog,
+        kernel,
+        size,
+        inputs,
+    }))
+}
+
+fn value_of(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("missing value for `{}`", flag))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value_of(flag, value)?;
+    value.parse().map_err(|_| format!("invalid value `{}` for `{}`", value, flag))
+}
+
+/// `<width>x<height>`, e.g. `16000x16000`.
+fn parse_size(flag: &str, value: Option<String>) -> Result<[u32; 2], String> {
+    let value = value_of(flag, value)?;
+    let invalid = || format!("invalid size `{}` for `{}`, expected <width>x<height>", value, flag);
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok([width, height]),
+        _ => Err(invalid()),
+    }
+}
+
+/// Expands `*` and `?` in the file name of the pattern, paths without wildcards are kept as is.
+fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let path = Path::new(pattern);
+    let file_pattern = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    if !file_pattern.contains(['*', '?']) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| wildcard_match(file_pattern, name))
+        })
+        .collect();
+    matches.sort();
+
+    if matches.is_empty() {
+        return Err(format!("`{}` did not match any file", pattern));
+    }
+    Ok(matches)
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        match parse_args(args.iter().map(|arg| arg.to_string()))? {
+            Command::Run(options) => Ok(options),
+            Command::Help => Err("help".to_string()),
+        }
+    }
+
+    fn parse_err(args: &[&str]) -> String {
+        match parse(args) {
+            Ok(_) => panic!("{:?} was accepted", args),
+            Err(err) => err,
+        }
+    }
+
+    fn size(value: &str) -> Result<[u32; 2], String> {
+        parse_size("--size", Some(value.to_string()))
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(size("1920x1080"), Ok([1920, 1080]));
+        assert_eq!(size("16000x1"), Ok([16000, 1]));
+    }
+
+    #[test]
+    fn rejects_malformed_sizes() {
+        for value in ["", "1920", "1920x", "x1080", "0x1080", "1920x0", "-1x10", "10x10x10", "1920*1080", "a x b"] {
+            assert!(size(value).is_err(), "`{}` was accepted", value);
+        }
+        assert!(parse_size("--size", None).is_err());
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*.jpg", "a.jpg"));
+        assert!(wildcard_match("*.jpg", ".jpg"));
+        assert!(wildcard_match("img_??.png", "img_01.png"));
+        assert!(wildcard_match("*_*.png", "a_b_c.png"));
+        assert!(wildcard_match("a*b*c", "aXXbYYc"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("exact.png", "exact.png"));
+
+        assert!(!wildcard_match("*.jpg", "a.jpeg"));
+        assert!(!wildcard_match("img_??.png", "img_1.png"));
+        assert!(!wildcard_match("img_??.png", "img_001.png"));
+        assert!(!wildcard_match("a*b*c", "aXXbYY"));
+        assert!(!wildcard_match("?", ""));
+    }
+
+    #[test]
+    fn names_outputs_after_inputs() {
+        let inputs = [PathBuf::from("in/a.jpg"), PathBuf::from("in/b.jpeg")];
+        let outputs = output_paths(&inputs, Path::new("out")).unwrap();
+        assert_eq!(outputs, [PathBuf::from("out/a.png"), PathBuf::from("out/b.png")]);
+
+        let inputs = [PathBuf::from("photo.v1.jpg"), PathBuf::from("photo.v2.jpg")];
+        let outputs = output_paths(&inputs, Path::new("out")).unwrap();
+        assert_eq!(outputs, [PathBuf::from("out/photo.v1.png"), PathBuf::from("out/photo.v2.png")]);
+    }
+
+    #[test]
+    fn rejects_inputs_writing_the_same_output() {
+        let inputs = [PathBuf::from("a.jpg"), PathBuf::from("b.jpg"), PathBuf::from("other/a.png")];
+        let err = output_paths(&inputs, Path::new("out")).unwrap_err();
+        assert!(err.contains("a.jpg") && err.contains("other/a.png"), "{}", err);
+    }
+
+    #[test]
+    fn parses_effect_names() {
+        let effects = ["dog", "convolution", "blur", "xdog", "fdog"];
+        for name in effects {
+            let options = parse(&["--effect", name, "--output", "out", "a.jpg"]).unwrap();
+            let parsed = match options.effect {
+                Effect::Dog => "dog",
+                Effect::Convolution => "convolution",
+                Effect::Blur => "blur",
+                Effect::Xdog => "xdog",
+                Effect::FlowDog => "fdog",
+            };
+            assert_eq!(parsed, name);
+        }
+        assert!(parse_err(&["--effect", "sobel", "--output", "out", "a.jpg"]).contains("sobel"));
+    }
+
+    #[test]
+    fn uses_defaults_for_omitted_options() {
+        let options = parse(&["--effect", "xdog", "--output", "out", "a.jpg", "b.png"]).unwrap();
+        assert_eq!(options.output, PathBuf::from("out"));
+        assert_eq!(options.inputs, [PathBuf::from("a.jpg"), PathBuf::from("b.png")]);
+        assert_eq!(options.accentuate, 1.0);
+        assert_eq!(options.time, 0.0);
+        assert_eq!(options.seed, 0);
+        assert_eq!(options.grain, 0.0);
+        assert_eq!(options.sigma, None);
+        assert_eq!(options.xdog, XdogParams::default());
+        assert_eq!(options.flow_dog, FlowDogParams::default());
+        assert_eq!(options.kernel, ConvolutionPreset::Identity);
+        assert_eq!(options.size, None);
+    }
+
+    #[test]
+    fn parses_option_values() {
+        let args = [
+            "--effect", "fdog", "--output", "out", "--sigma", "1.5", "--tau", "12", "--seed", "7",
+            "--sigma-m", "4", "--kernel", "sharpen", "--size", "640x480", "a.jpg",
+        ];
+        let options = parse(&args).unwrap();
+        assert_eq!(options.sigma, Some(1.5));
+        assert_eq!(options.xdog.tau, 12.0);
+        assert_eq!(options.seed, 7);
+        assert_eq!(options.flow_dog.sigma_m, 4.0);
+        assert_eq!(options.kernel, ConvolutionPreset::Sharpen);
+        assert_eq!(options.size, Some([640, 480]));
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(parse_err(&["a.jpg", "--output", "out", "--effect"]), "missing value for `--effect`");
+        assert_eq!(parse_err(&["--effect", "dog", "a.jpg", "--output"]), "missing value for `--output`");
+        assert_eq!(
+            parse_err(&["--effect", "dog", "--output", "out", "a.jpg", "--sigma"]),
+            "missing value for `--sigma`"
+        );
+        assert!(parse_err(&["--effect", "dog", "--output", "out", "--tau", "strong", "a.jpg"]).contains("strong"));
+        assert_eq!(parse_err(&["--output", "out", "a.jpg"]), "missing --effect");
+        assert_eq!(parse_err(&["--effect", "dog", "a.jpg"]), "missing --output");
+        assert_eq!(parse_err(&["--effect", "dog", "--output", "out"]), "no input images");
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        let err = parse_err(&["--effect", "dog", "--output", "out", "--strength", "2", "a.jpg"]);
+        assert_eq!(err, "unknown option `--strength`");
+        assert!(parse_err(&["--effect", "blur", "--output", "out", "--kernel", "gauss", "a.jpg"]).contains("gauss"));
+    }
+
+    #[test]
+    fn asks_for_help() {
+        for flag in ["-h", "--help"] {
+            let command = parse_args([flag, "--effect", "bogus"].iter().map(|arg| arg.to_string()));
+            assert!(matches!(command, Ok(Command::Help)));
+        }
+    }
+}
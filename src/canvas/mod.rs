@@ -0,0 +1,150 @@
+use nannou::image::RgbaImage;
+use nannou::prelude::DeviceExt;
+use nannou::wgpu::BufferInitDescriptor;
+use nannou::{wgpu, Frame};
+
+use crate::canvas::tiled::TileUniform;
+use crate::reflection::{BindResource, Bindings, ShaderReflection};
+use crate::shader_processing::model::{Vert, QUAD};
+use crate::shader_processing::offscreen::{read_texture, OffscreenError, OFFSCREEN_TEXTURE_FORMAT};
+use crate::shader_processing::pipeline::tiled_vertex_shader;
+use crate::uniforms::UniformBuffer;
+use crate::viewport::Viewport;
+
+pub mod tiled;
+
+/// A texture that sketches render into at a resolution of their own, shown scaled in the window
+/// by a `CanvasPreview` and read back for exports.
+pub struct Canvas {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl Canvas {
+    /// A canvas of `OFFSCREEN_TEXTURE_FORMAT`, so that it can be read back as is.
+    pub fn new(device: &wgpu::Device, size: [u32; 2]) -> Self {
+        let texture = wgpu::TextureBuilder::new()
+            .size(size)
+            .format(OFFSCREEN_TEXTURE_FORMAT)
+            .usage(
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+            )
+            .build(device);
+        let view = texture.view().build();
+        Canvas { texture, view }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.texture.size()
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        OFFSCREEN_TEXTURE_FORMAT
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// The view to render into.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Copies the canvas back into CPU memory, blocking until the GPU is done with it.
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<RgbaImage, OffscreenError> {
+        read_texture(device, queue, &self.texture)
+    }
+}
+
+/// Draws a `Canvas` into a window where a `Viewport` places it, as large as it fits until
+/// `set_viewport` is called.
+///
+/// Bound to the texture of the canvas, a new one is needed when the canvas is replaced.
+pub struct CanvasPreview {
+    bindings: Bindings,
+    render_pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    tile_uniform: UniformBuffer<TileUniform>,
+    canvas_size: [u32; 2],
+}
+
+impl CanvasPreview {
+    /// `format` and `msaa_samples` are the ones of the target, e.g. `Frame::TEXTURE_FORMAT` and
+    /// `window.msaa_samples()`.
+    pub fn new(device: &wgpu::Device, canvas: &Canvas, format: wgpu::TextureFormat, msaa_samples: u32) -> Self {
+        let vs_source = tiled_vertex_shader();
+        let fs_source = include_str!("shaders/preview.wgsl");
+        let vs_mod = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("tiled_vs.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(vs_source.as_str().into()),
+        });
+        let fs_mod = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("preview.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(fs_source.into()),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerBuilder::new().into_descriptor());
+        let tile_uniform = UniformBuffer::new(device, "canvas-preview-tile", TileUniform::FULL);
+        let resources = [
+            ("tex", BindResource::TextureView(canvas.view())),
+            ("tex_sampler", BindResource::Sampler(&sampler)),
+            ("tile", BindResource::Buffer(tile_uniform.buffer())),
+        ];
+        let bindings = ShaderReflection::from_wgsl(fs_source)
+            .and_then(|reflection| Ok(reflection.merge(ShaderReflection::from_wgsl(&vs_source)?)))
+            .map_err(|err| err.to_string())
+            .and_then(|reflection| reflection.bind(device, &resources).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| panic!("canvas preview: {}", err));
+        let pipeline_layout = bindings.pipeline_layout(device, "canvas-preview");
+
+        let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vs_mod)
+            .fragment_shader(&fs_mod)
+            .color_format(format)
+            .add_vertex_buffer::<Vert>(&wgpu::vertex_attr_array![0 => Float32x2])
+            .sample_count(msaa_samples)
+            .primitive_topology(wgpu::PrimitiveTopology::TriangleStrip)
+            .build(device);
+
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("canvas-preview"),
+            contents: bytemuck::cast_slice(&QUAD[..]),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        CanvasPreview {
+            bindings,
+            render_pipeline,
+            vertex_buffer,
+            tile_uniform,
+            canvas_size: canvas.size(),
+        }
+    }
+
+    /// Places the canvas in a target of `target_size` pixels, from the next submitted frame.
+    pub fn set_viewport(&mut self, queue: &wgpu::Queue, viewport: &Viewport, target_size: [u32; 2]) {
+        let rect = viewport.rect(self.canvas_size, target_size.map(|side| side as f32));
+        self.tile_uniform.set(TileUniform::for_rect(rect, target_size));
+        self.tile_uniform.flush(queue);
+    }
+
+    /// Records the draw into the whole target, black around the canvas.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureViewHandle) {
+        let mut render_pass = wgpu::RenderPassBuilder::new()
+            .color_attachment(target, |color| color)
+            .begin(encoder);
+        for (i, bind_group) in self.bindings.groups().iter().enumerate() {
+            render_pass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..QUAD.len() as u32, 0..1);
+    }
+
+    pub fn draw_to_frame(&self, frame: &Frame) {
+        let mut encoder = frame.command_encoder();
+        self.encode(&mut encoder, frame.texture_view());
+    }
+}
@@ -0,0 +1,16 @@
+struct FragmentOutput {
+    @location(0) f_color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var tex: texture_2d<f32>;
+@group(0) @binding(1)
+var tex_sampler: sampler;
+
+@fragment
+fn main(@location(0) tex_coords: vec2<f32>) -> FragmentOutput {
+    let color = textureSample(tex, tex_sampler, tex_coords);
+    // Black around the canvas, rather than its edges stretched
+    let inside = all(tex_coords >= vec2<f32>(0.0)) && all(tex_coords <= vec2<f32>(1.0));
+    return FragmentOutput(select(vec4<f32>(0.0, 0.0, 0.0, 1.0), color, inside));
+}
@@ -0,0 +1,225 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use nannou::image::{imageops, RgbaImage};
+use nannou::wgpu;
+
+use crate::shader_processing::model::ShaderModel;
+use crate::shader_processing::offscreen::{create_render_target, read_texture, OffscreenError};
+use crate::shader_processing::pipeline::encode_render_pass;
+use crate::uniforms::ShaderUniform;
+
+/// Side of the tiles, well under the 8192 pixels textures are limited to by default.
+pub const DEFAULT_TILE_SIZE: u32 = 4096;
+
+/// The part of the target a `ShaderModel` renders, read by `tiled_vs.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
+pub struct TileUniform {
+    /// Texture coordinates of the top left corner of the tile.
+    pub offset: [f32; 2],
+    /// Size of the tile in texture coordinates.
+    pub scale: [f32; 2],
+}
+
+impl TileUniform {
+    /// The whole target in one go.
+    pub const FULL: TileUniform = TileUniform {
+        offset: [0.0, 0.0],
+        scale: [1.0, 1.0],
+    };
+
+    /// Draws the whole texture into `rect`, `[x, y, width, height]` in pixels of a target of
+    /// `target_size` (e.g. a `Viewport::rect`). Around it the texture coordinates are outside of
+    /// `[0, 1]`.
+    pub fn for_rect(rect: [f32; 4], target_size: [u32; 2]) -> Self {
+        let [x, y, width, height] = rect;
+        let [target_width, target_height] = target_size.map(|side| side as f32);
+        TileUniform {
+            offset: [-x / width, -y / height],
+            scale: [target_width / width, target_height / height],
+        }
+    }
+
+    /// A tile of an image of `size` pixels.
+    pub fn new(tile: &Tile, size: [u32; 2]) -> Self {
+        let [width, height] = size.map(|side| side as f32);
+        TileUniform {
+            offset: [tile.origin[0] as f32 / width, tile.origin[1] as f32 / height],
+            scale: [tile.size[0] as f32 / width, tile.size[1] as f32 / height],
+        }
+    }
+}
+
+/// A rectangle of pixels of a larger image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    pub origin: [u32; 2],
+    pub size: [u32; 2],
+}
+
+/// Splits an image of `size` pixels into rows of tiles of at most `tile_size` pixels, from the
+/// top left corner.
+pub fn tile_rows(size: [u32; 2], tile_size: u32) -> Vec<Vec<Tile>> {
+    let [width, height] = size;
+    (0..height)
+        .step_by(tile_size as usize)
+        .map(|y| {
+            (0..width)
+                .step_by(tile_size as usize)
+                .map(|x| Tile {
+                    origin: [x, y],
+                    size: [tile_size.min(width - x), tile_size.min(height - y)],
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Renders a `ShaderModel` at `size`, one tile at a time, and hands each row of tiles stitched
+/// together to `write_strip`, from top to bottom. Only a strip is ever in memory, so `size` is
+/// only limited by what `write_strip` does with them.
+///
+/// Every pixel gets the same texture coordinates as in a single render of `size` up to rounding,
+/// there are no seams between the tiles. The model renders the whole target again afterwards.
+pub fn render_strips<F, E>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    shader_model: &mut ShaderModel,
+    size: [u32; 2],
+    tile_size: u32,
+    mut write_strip: F,
+) -> Result<(), E>
+where
+    F: FnMut(RgbaImage) -> Result<(), E>,
+    E: From<OffscreenError>,
+{
+    let result = tile_rows(size, tile_size).iter().try_for_each(|row| {
+        let mut strip = RgbaImage::new(size[0], row[0].size[1]);
+        for tile in row {
+            shader_model.set_tile(queue, TileUniform::new(tile, size));
+            let target = create_render_target(device, tile.size);
+            let desc = wgpu::CommandEncoderDescriptor {
+                label: Some("tiled-render"),
+            };
+            let mut encoder = device.create_command_encoder(&desc);
+            encode_render_pass(&mut encoder, &target.view().build(), shader_model);
+            queue.submit(Some(encoder.finish()));
+            imageops::replace(&mut strip, &read_texture(device, queue, &target)?, tile.origin[0], 0);
+        }
+        write_strip(strip)
+    });
+    shader_model.set_tile(queue, TileUniform::FULL);
+    result
+}
+
+/// `render_strips` into a single image, for sizes that fit in memory.
+pub fn render_tiled(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    shader_model: &mut ShaderModel,
+    size: [u32; 2],
+    tile_size: u32,
+) -> Result<RgbaImage, OffscreenError> {
+    let mut image = RgbaImage::new(size[0], size[1]);
+    let mut y = 0;
+    render_strips(device, queue, shader_model, size, tile_size, |strip| {
+        imageops::replace(&mut image, &strip, 0, y);
+        y += strip.height();
+        Ok::<_, OffscreenError>(())
+    })?;
+    Ok(image)
+}
+
+/// `render_strips` streamed into a PNG file, for prints too large to hold in memory.
+pub fn render_tiled_png<P: AsRef<Path>>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    shader_model: &mut ShaderModel,
+    size: [u32; 2],
+    tile_size: u32,
+    path: P,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path.as_ref()).map_err(|err| format!("{}: {}", path.as_ref().display(), err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size[0], size[1]);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    render_strips(device, queue, shader_model, size, tile_size, |strip| {
+        stream.write_all(strip.as_raw()).map_err(Box::<dyn Error>::from)
+    })?;
+    stream.finish()?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the tiles cover `size` exactly once, row by row from the top left corner.
+    fn assert_covers(rows: &[Vec<Tile>], size: [u32; 2]) {
+        let mut y = 0;
+        for row in rows {
+            let mut x = 0;
+            for tile in row {
+                assert_eq!(tile.origin, [x, y]);
+                assert_eq!(tile.size[1], row[0].size[1]);
+                x += tile.size[0];
+            }
+            assert_eq!(x, size[0]);
+            y += row[0].size[1];
+        }
+        assert_eq!(y, size[1]);
+    }
+
+    #[test]
+    fn tiles_cover_the_image_exactly() {
+        let rows = tile_rows([8192, 4096], 4096);
+        assert_covers(&rows, [8192, 4096]);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].iter().all(|tile| tile.size == [4096, 4096]));
+
+        for size in [[1, 1], [100, 100], [4097, 3], [12000, 9000]] {
+            assert_covers(&tile_rows(size, 4096), size);
+        }
+    }
+
+    #[test]
+    fn shrinks_the_last_row_and_column() {
+        let rows = tile_rows([10, 7], 4);
+        assert_covers(&rows, [10, 7]);
+        let sizes: Vec<Vec<[u32; 2]>> = rows.iter().map(|row| row.iter().map(|tile| tile.size).collect()).collect();
+        assert_eq!(sizes, [vec![[4, 4], [4, 4], [2, 4]], vec![[4, 3], [4, 3], [2, 3]]]);
+    }
+
+    #[test]
+    fn maps_tiles_to_their_part_of_the_image() {
+        let tile = Tile {
+            origin: [4, 4],
+            size: [2, 3],
+        };
+        let uniform = TileUniform::new(&tile, [10, 7]);
+        assert_eq!(uniform.offset, [0.4, 4.0 / 7.0]);
+        assert_eq!(uniform.scale, [0.2, 3.0 / 7.0]);
+
+        let whole = Tile {
+            origin: [0, 0],
+            size: [10, 7],
+        };
+        assert_eq!(TileUniform::new(&whole, [10, 7]), TileUniform::FULL);
+    }
+
+    #[test]
+    fn draws_into_the_full_target_like_a_single_render() {
+        assert_eq!(TileUniform::for_rect([0.0, 0.0, 1920.0, 1080.0], [1920, 1080]), TileUniform::FULL);
+
+        // Half the size, centered
+        let uniform = TileUniform::for_rect([480.0, 270.0, 960.0, 540.0], [1920, 1080]);
+        assert_eq!(uniform.offset, [-0.5, -0.5]);
+        assert_eq!(uniform.scale, [2.0, 2.0]);
+    }
+}
@@ -0,0 +1,97 @@
+use nannou::wgpu;
+
+use crate::compute_kernel::gaussian_blur::GaussianBlurPasses;
+use crate::controls::UniformControls;
+use crate::interpolation::Interpolate;
+use crate::render_graph::{PassHandle, RenderGraph, RenderGraphBuilder, RenderGraphError};
+use crate::uniforms::ShaderUniform;
+
+/// Standard deviation of the narrow blur of the difference of gaussians, in pixels.
+pub const DOG_SIGMA: f32 = 1.0;
+/// Standard deviation of the wide blur, subtracted from the narrow one.
+pub const DOG_WIDE_SIGMA: f32 = 1.4;
+
+/// Uniforms of the edges pass of a `Dog`, shaders get them with
+/// `#include "uniforms/DogUniforms.wgsl"`.
+///
+/// Missing fields take their default value when deserializing, so older presets keep loading.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform, UniformControls)]
+#[derive(serde::Serialize, serde::Deserialize, Interpolate)]
+#[serde(default)]
+pub struct DogUniforms {
+    #[control(color, label = "Edge color")]
+    #[interpolate(color)]
+    pub color: [f32; 3],
+    /// Gain of the difference, the edges are faint below `1`.
+    #[control(slider(1.0..=20.0))]
+    pub accentuate: f32,
+    /// Non zero for dark edges on white.
+    #[control(toggle)]
+    #[interpolate(step)]
+    pub invert: u32,
+    /// Seconds since the start, for animated shaders.
+    #[serde(skip)]
+    #[interpolate(skip)]
+    pub time: f32,
+    /// Seed of the grain noise.
+    #[serde(skip)]
+    #[interpolate(skip)]
+    pub seed: u32,
+    /// Amount of noise added to the edges.
+    #[control(slider(0.0..=1.0))]
+    pub grain: f32,
+}
+
+impl Default for DogUniforms {
+    fn default() -> Self {
+        DogUniforms {
+            color: [1.0; 3],
+            accentuate: 1.0,
+            invert: 0,
+            time: 0.0,
+            seed: 0,
+            grain: 0.0,
+        }
+    }
+}
+
+/// Difference of gaussians as a render graph: an optional pre-blur of the source, the narrow and
+/// the wide blur of that, and an edges pass combining both.
+///
+/// The edges pass is given as WGSL (e.g. `examples/shaders/cs.wgsl`, which can be hot reloaded)
+/// and reads the narrow then the wide blur after its `DogUniforms`.
+pub struct Dog {
+    pub graph: RenderGraph,
+    /// Starts with a sigma of `0`, which leaves the source as is.
+    pub pre_blur: GaussianBlurPasses,
+    pub edges: PassHandle<DogUniforms>,
+}
+
+impl Dog {
+    /// `output` must be a storage texture of the same size as `input`.
+    pub fn new(
+        device: &wgpu::Device,
+        edges_wgsl: &str,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+        uniforms: DogUniforms,
+    ) -> Result<Self, RenderGraphError> {
+        let mut builder = RenderGraphBuilder::new(input.size());
+        builder.import("source", input);
+        builder.import("output", output);
+        let pre_blur = GaussianBlurPasses::add_to_graph(&mut builder, "pre_blur", "source", "blurred", 0.0);
+        GaussianBlurPasses::add_to_graph(&mut builder, "narrow_blur", "blurred", "narrow", DOG_SIGMA);
+        GaussianBlurPasses::add_to_graph(&mut builder, "wide_blur", "blurred", "wide", DOG_WIDE_SIGMA);
+        let edges = builder.compute_pass("edges", edges_wgsl, &["narrow", "wide"], "output", uniforms);
+        Ok(Dog {
+            graph: builder.build(device)?,
+            pre_blur,
+            edges,
+        })
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.graph.encode(encoder);
+    }
+}
@@ -0,0 +1,191 @@
+use nannou::wgpu;
+
+use crate::compute_kernel::{ComputeKernel, create_storage_texture_with_format, WorkgroupSize};
+use crate::compute_kernel::gaussian_blur::{GaussianBlur, INTERMEDIATE_TEXTURE_FORMAT};
+use crate::controls::UniformControls;
+use crate::interpolation::Interpolate;
+use crate::uniforms::ShaderUniform;
+
+/// Parameters of the flow-based difference of gaussians, see `XdogParams` for deserializing.
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize, Interpolate, UniformControls)]
+#[serde(default)]
+pub struct FlowDogParams {
+    /// Standard deviation of the blur smoothing the structure tensor, larger values give a
+    /// smoother and more coherent flow.
+    #[control(slider(0.1..=10.0), label = "Flow smoothing")]
+    pub tensor_sigma: f32,
+    /// Standard deviation of the narrow blur across the edges, in pixels.
+    #[control(slider(0.1..=5.0), label = "Sigma across edges")]
+    pub sigma_e: f32,
+    /// Ratio between the wide and the narrow blur.
+    #[control(slider(1.0..=5.0))]
+    pub k: f32,
+    /// Standard deviation of the smoothing along the edges, in pixels.
+    #[control(slider(0.1..=10.0), label = "Sigma along edges")]
+    pub sigma_m: f32,
+    /// Distance between two samples when following the flow, in pixels.
+    #[control(slider(0.25..=2.0))]
+    pub step_size: f32,
+    /// Sharpening weight, `0` leaves the narrow blur as is.
+    #[control(slider(0.0..=100.0))]
+    pub tau: f32,
+    /// Threshold above which the result is white.
+    #[control(slider(0.0..=1.0))]
+    pub epsilon: f32,
+    /// Steepness of the transition to black below `epsilon`.
+    #[control(slider(0.0..=100.0))]
+    pub phi: f32,
+    /// `0` outputs the grey edge map, `1` multiplies it with the source colors.
+    #[control(slider(0.0..=1.0))]
+    pub color_blend: f32,
+}
+
+impl Default for FlowDogParams {
+    fn default() -> Self {
+        FlowDogParams {
+            tensor_sigma: 2.0,
+            sigma_e: 1.0,
+            k: 1.6,
+            sigma_m: 3.0,
+            step_size: 1.0,
+            tau: 20.0,
+            epsilon: 0.6,
+            phi: 10.0,
+            color_blend: 0.0,
+        }
+    }
+}
+
+/// Shared by every pass of the flow-based DoG, each one reads what it needs.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
+pub struct FlowDogUniforms {
+    pub sigma_e: f32,
+    pub k: f32,
+    pub sigma_m: f32,
+    pub step_size: f32,
+    pub tau: f32,
+    pub epsilon: f32,
+    pub phi: f32,
+    pub color_blend: f32,
+}
+
+impl From<&FlowDogParams> for FlowDogUniforms {
+    fn from(params: &FlowDogParams) -> Self {
+        FlowDogUniforms {
+            sigma_e: params.sigma_e,
+            k: params.k,
+            sigma_m: params.sigma_m,
+            step_size: params.step_size,
+            tau: params.tau,
+            epsilon: params.epsilon,
+            phi: params.phi,
+            color_blend: params.color_blend,
+        }
+    }
+}
+
+/// Coherent line drawing: the DoG is computed across the edges of an edge tangent flow (ETF) and
+/// smoothed along them, instead of isotropically.
+///
+/// The passes are:
+/// 1. the structure tensor of the source lightness,
+/// 2. a gaussian blur of the tensor,
+/// 3. the ETF, a vector field with the tangent in `xy` and the anisotropy in `z`,
+/// 4. both gaussian blurs along the gradient of the flow,
+/// 5. a line integral convolution of those along the flow, followed by the XDoG threshold.
+pub struct FlowDog {
+    structure_tensor: ComputeKernel<FlowDogUniforms>,
+    tensor_blur: GaussianBlur,
+    edge_tangent_flow: ComputeKernel<FlowDogUniforms>,
+    gradient_dog: ComputeKernel<FlowDogUniforms>,
+    flow_dog: ComputeKernel<FlowDogUniforms>,
+    flow_field: wgpu::Texture,
+    params: FlowDogParams,
+}
+
+impl FlowDog {
+    /// `output` must be a storage texture of the same size as `input`.
+    pub fn new(device: &wgpu::Device, input: &wgpu::Texture, output: &wgpu::Texture, params: FlowDogParams) -> Self {
+        let size = input.size();
+        let workgroup_size = WorkgroupSize::default();
+        let uniforms = FlowDogUniforms::from(&params);
+        let tensor = create_storage_texture_with_format(device, size, INTERMEDIATE_TEXTURE_FORMAT);
+        let smoothed_tensor = create_storage_texture_with_format(device, size, INTERMEDIATE_TEXTURE_FORMAT);
+        let flow_field = create_storage_texture_with_format(device, size, INTERMEDIATE_TEXTURE_FORMAT);
+        let gradient_blurs = create_storage_texture_with_format(device, size, INTERMEDIATE_TEXTURE_FORMAT);
+
+        let structure_tensor = ComputeKernel::new(
+            device,
+            include_str!("shaders/structure_tensor.wgsl"),
+            workgroup_size,
+            input,
+            &tensor,
+            uniforms,
+        );
+        let tensor_blur = GaussianBlur::new(device, &tensor, &smoothed_tensor, params.tensor_sigma);
+        let edge_tangent_flow = ComputeKernel::new(
+            device,
+            include_str!("shaders/edge_tangent_flow.wgsl"),
+            workgroup_size,
+            &smoothed_tensor,
+            &flow_field,
+            uniforms,
+        );
+        let gradient_dog = ComputeKernel::with_inputs(
+            device,
+            include_str!("shaders/flow_dog_gradient.wgsl"),
+            workgroup_size,
+            &[input, &flow_field],
+            &gradient_blurs,
+            uniforms,
+        );
+        let flow_dog = ComputeKernel::with_inputs(
+            device,
+            include_str!("shaders/flow_dog.wgsl"),
+            workgroup_size,
+            &[input, &flow_field, &gradient_blurs],
+            output,
+            uniforms,
+        );
+
+        FlowDog {
+            structure_tensor,
+            tensor_blur,
+            edge_tangent_flow,
+            gradient_dog,
+            flow_dog,
+            flow_field,
+            params,
+        }
+    }
+
+    pub fn params(&self) -> &FlowDogParams {
+        &self.params
+    }
+
+    /// Updates the parameters, only what changed is uploaded to the GPU.
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: FlowDogParams) {
+        if params.tensor_sigma != self.params.tensor_sigma {
+            self.tensor_blur.set_sigma(queue, params.tensor_sigma);
+        }
+        let uniforms = FlowDogUniforms::from(&params);
+        self.gradient_dog.write_uniforms(queue, uniforms);
+        self.flow_dog.write_uniforms(queue, uniforms);
+        self.params = params;
+    }
+
+    /// The edge tangent flow, `Rgba16Float` with the unit tangent in `xy` and the anisotropy
+    /// (`0` for flat areas, `1` for straight edges) in `z`.
+    pub fn flow_field(&self) -> &wgpu::Texture {
+        &self.flow_field
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.structure_tensor.dispatch(encoder);
+        self.tensor_blur.dispatch(encoder);
+        self.edge_tangent_flow.dispatch(encoder);
+        self.gradient_dog.dispatch(encoder);
+        self.flow_dog.dispatch(encoder);
+    }
+}
@@ -0,0 +1,153 @@
+use nannou::wgpu;
+
+use crate::compute_kernel::{ComputeKernel, create_storage_texture_with_format, WorkgroupSize};
+use crate::render_graph::{PassHandle, RenderGraph, RenderGraphBuilder};
+use crate::uniforms::ShaderUniform;
+
+/// Weights packed in `BlurUniforms`, the blur reads `radius + 1` of them since it is symmetric.
+pub const MAX_BLUR_WEIGHTS: usize = 128;
+pub const MAX_BLUR_RADIUS: u32 = MAX_BLUR_WEIGHTS as u32 - 1;
+
+/// The intermediate result between both passes keeps more precision than the 8 bit output.
+pub const INTERMEDIATE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
+pub struct BlurUniforms {
+    // Weights for offsets 0..=radius, packed four per vec4
+    pub weights: [[f32; 4]; MAX_BLUR_WEIGHTS / 4],
+    pub direction: [u32; 2],
+    pub radius: u32,
+    pub _padding: u32,
+}
+
+impl BlurUniforms {
+    /// Normalized weights of a gaussian with the given standard deviation, covering `3 * sigma`.
+    pub fn new(sigma: f32, direction: [u32; 2]) -> Self {
+        let radius = blur_radius(sigma);
+        let mut weights = [[0.0; 4]; MAX_BLUR_WEIGHTS / 4];
+        if radius == 0 {
+            weights[0][0] = 1.0;
+        } else {
+            let mut sum = 0.0;
+            for i in 0..=radius as usize {
+                let weight = (-((i * i) as f32) / (2.0 * sigma * sigma)).exp();
+                weights[i / 4][i % 4] = weight;
+                // Every weight but the center one is used twice
+                sum += if i == 0 { weight } else { 2.0 * weight };
+            }
+            for weight in weights.iter_mut().flatten() {
+                *weight /= sum;
+            }
+        }
+        BlurUniforms {
+            weights,
+            direction,
+            radius,
+            _padding: 0,
+        }
+    }
+}
+
+/// Amount of pixels sampled at each side of the center for a given sigma.
+pub fn blur_radius(sigma: f32) -> u32 {
+    ((sigma * 3.0).ceil().max(0.0) as u32).min(MAX_BLUR_RADIUS)
+}
+
+/// A gaussian blur done in two passes, horizontal then vertical, so its cost grows linearly with
+/// the radius instead of quadratically.
+pub struct GaussianBlur {
+    horizontal: ComputeKernel<BlurUniforms>,
+    vertical: ComputeKernel<BlurUniforms>,
+    intermediate: wgpu::Texture,
+    sigma: f32,
+}
+
+impl GaussianBlur {
+    /// `output` must be a storage texture of the same size as `input`.
+    pub fn new(device: &wgpu::Device, input: &wgpu::Texture, output: &wgpu::Texture, sigma: f32) -> Self {
+        let source = include_str!("shaders/gaussian_blur.wgsl");
+        let workgroup_size = WorkgroupSize::default();
+        let intermediate = create_storage_texture_with_format(device, input.size(), INTERMEDIATE_TEXTURE_FORMAT);
+        let horizontal = ComputeKernel::new(
+            device,
+            source,
+            workgroup_size,
+            input,
+            &intermediate,
+            BlurUniforms::new(sigma, [1, 0]),
+        );
+        let vertical = ComputeKernel::new(
+            device,
+            source,
+            workgroup_size,
+            &intermediate,
+            output,
+            BlurUniforms::new(sigma, [0, 1]),
+        );
+        GaussianBlur {
+            horizontal,
+            vertical,
+            intermediate,
+            sigma,
+        }
+    }
+
+    pub fn sigma(&self) -> f32 {
+        self.sigma
+    }
+
+    /// Recomputes the weights, they will be used from the next submitted dispatch.
+    pub fn set_sigma(&mut self, queue: &wgpu::Queue, sigma: f32) {
+        self.sigma = sigma;
+        self.horizontal.write_uniforms(queue, BlurUniforms::new(sigma, [1, 0]));
+        self.vertical.write_uniforms(queue, BlurUniforms::new(sigma, [0, 1]));
+    }
+
+    /// The horizontally blurred image.
+    pub fn intermediate(&self) -> &wgpu::Texture {
+        &self.intermediate
+    }
+
+    /// Records both passes, the vertical one reads what the horizontal one wrote.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.horizontal.dispatch(encoder);
+        self.vertical.dispatch(encoder);
+    }
+}
+
+/// Both passes of a `GaussianBlur` added to a render graph, see `add_to_graph`.
+#[derive(Copy, Clone)]
+pub struct GaussianBlurPasses {
+    pub horizontal: PassHandle<BlurUniforms>,
+    pub vertical: PassHandle<BlurUniforms>,
+}
+
+impl GaussianBlurPasses {
+    /// Adds the passes blurring `input` into `output`, through a `<name>.horizontal` texture.
+    pub fn add_to_graph(builder: &mut RenderGraphBuilder, name: &str, input: &str, output: &str, sigma: f32) -> Self {
+        let source = include_str!("shaders/gaussian_blur.wgsl");
+        let intermediate = format!("{}.horizontal", name);
+        builder.texture_format(&intermediate, INTERMEDIATE_TEXTURE_FORMAT);
+        let horizontal = builder.compute_pass(
+            &intermediate,
+            source,
+            &[input],
+            &intermediate,
+            BlurUniforms::new(sigma, [1, 0]),
+        );
+        let vertical = builder.compute_pass(
+            &format!("{}.vertical", name),
+            source,
+            &[&intermediate],
+            output,
+            BlurUniforms::new(sigma, [0, 1]),
+        );
+        GaussianBlurPasses { horizontal, vertical }
+    }
+
+    pub fn set_sigma(&self, graph: &mut RenderGraph, queue: &wgpu::Queue, sigma: f32) {
+        graph.write_uniforms(queue, self.horizontal, BlurUniforms::new(sigma, [1, 0]));
+        graph.write_uniforms(queue, self.vertical, BlurUniforms::new(sigma, [0, 1]));
+    }
+}
@@ -0,0 +1,257 @@
+use nannou::wgpu;
+
+use crate::preprocessor::Preprocessor;
+use crate::reflection::{BindResource, Bindings, ShaderReflection};
+use crate::uniforms::{UniformBuffer, WgslUniform};
+
+pub mod dog;
+pub mod flow_dog;
+pub mod gaussian_blur;
+pub mod ping_pong;
+pub mod xdog;
+
+/// Format used for the output of every kernel, it can be both written from a compute shader and
+/// sampled from a fragment shader.
+pub const STORAGE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Defined to the configured `WorkgroupSize` when preprocessing a kernel.
+///
+/// WGSL (as supported by our wgpu version) only accepts literals inside `@workgroup_size`, so
+/// these are defines instead of pipeline overridable constants.
+pub const WORKGROUP_SIZE_X: &str = "WORKGROUP_SIZE_X";
+pub const WORKGROUP_SIZE_Y: &str = "WORKGROUP_SIZE_Y";
+
+/// Defined to the format of `outTexture`, so the same kernel can write to any supported storage
+/// format (e.g. `rgba16float` for intermediate results).
+pub const OUTPUT_FORMAT: &str = "OUTPUT_FORMAT";
+
+/// Number of invocations per workgroup along `x` and `y`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WorkgroupSize {
+    x: u32,
+    y: u32,
+}
+
+impl WorkgroupSize {
+    /// Panics if `x` or `y` is 0, a workgroup has at least one invocation along each axis.
+    pub const fn new(x: u32, y: u32) -> Self {
+        assert!(x > 0 && y > 0, "workgroup sizes must be at least 1");
+        WorkgroupSize { x, y }
+    }
+
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Amount of workgroups needed to cover every pixel of a texture of the given size.
+    ///
+    /// Sizes that are not a multiple of the workgroup size are rounded up, so the shader is
+    /// expected to discard the invocations that fall outside of the texture.
+    pub fn workgroup_count(&self, size: [u32; 2]) -> [u32; 2] {
+        [size[0].div_ceil(self.x), size[1].div_ceil(self.y)]
+    }
+
+    /// Defines `WORKGROUP_SIZE_X` and `WORKGROUP_SIZE_Y`.
+    pub fn define(&self, preprocessor: Preprocessor) -> Preprocessor {
+        preprocessor
+            .with_define(WORKGROUP_SIZE_X, &format!("{}u", self.x))
+            .with_define(WORKGROUP_SIZE_Y, &format!("{}u", self.y))
+    }
+}
+
+impl Default for WorkgroupSize {
+    fn default() -> Self {
+        WorkgroupSize::new(8, 8)
+    }
+}
+
+/// A compute shader that reads from an input texture and writes into an output storage texture.
+///
+/// The WGSL module is expected to declare its resources as:
+///
+/// ```wgsl
+/// @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+/// @group(0) @binding(1) var inTexture: texture_2d<f32>;
+/// @group(0) @binding(2) var outTexture: texture_storage_2d<rgba8unorm, write>;
+///
+/// @compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+/// fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+///     let dimensions = textureDimensions(outTexture);
+///     if (id.x >= dimensions.x || id.y >= dimensions.y) {
+///         return;
+///     }
+///     ...
+/// }
+/// ```
+///
+/// Where `Uniforms` mirrors the Rust type `U`, it can be included as
+/// `#include "uniforms/<name>.wgsl"` as the source is preprocessed with `Preprocessor::with_uniform`
+/// and `WORKGROUP_SIZE_X`, `WORKGROUP_SIZE_Y` and `OUTPUT_FORMAT` defined. Kernels created with `with_inputs` read from
+/// `@binding(1)` up to `@binding(n)` and write to `@binding(n + 1)`. The variable names don't
+/// matter, the layout is reflected from the shader.
+pub struct ComputeKernel<U: WgslUniform> {
+    uniforms: UniformBuffer<U>,
+    bindings: Bindings,
+    pipeline: wgpu::ComputePipeline,
+    size: [u32; 2],
+    workgroup_size: WorkgroupSize,
+}
+
+impl<U: WgslUniform> ComputeKernel<U> {
+    pub fn new(
+        device: &wgpu::Device,
+        wgsl: &str,
+        workgroup_size: WorkgroupSize,
+        input: &wgpu::Texture,
+        output: &wgpu::Texture,
+        uniforms: U,
+    ) -> Self {
+        Self::with_inputs(device, wgsl, workgroup_size, &[input], output, uniforms)
+    }
+
+    /// A kernel that combines several input textures into one output.
+    pub fn with_inputs(
+        device: &wgpu::Device,
+        wgsl: &str,
+        workgroup_size: WorkgroupSize,
+        inputs: &[&wgpu::Texture],
+        output: &wgpu::Texture,
+        uniforms: U,
+    ) -> Self {
+        let preprocessor = Preprocessor::new()
+            .with_uniform::<U>()
+            .with_define(OUTPUT_FORMAT, storage_format_name(output.format()));
+        let source = workgroup_size
+            .define(preprocessor)
+            .process("compute-kernel.wgsl", wgsl)
+            .unwrap_or_else(|err| panic!("compute kernel: {}", err))
+            .source;
+        let cs_mod = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("compute-kernel"),
+            source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+        });
+
+        let uniforms = UniformBuffer::new(device, "compute-kernel-uniforms", uniforms);
+
+        let input_views: Vec<_> = inputs.iter().map(|input| input.view().build()).collect();
+        let output_view = output.view().build();
+        let mut resources = vec![BindResource::Buffer(uniforms.buffer())];
+        resources.extend(input_views.iter().map(BindResource::TextureView));
+        resources.push(BindResource::TextureView(&output_view));
+
+        let bindings = ShaderReflection::bind_or_panic(&[&source], "compute kernel", |reflection| {
+            reflection.bind_in_order(device, &resources)
+        });
+        let pipeline_layout = bindings.pipeline_layout(device, "compute-kernel");
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("compute-kernel"),
+            layout: Some(&pipeline_layout),
+            module: &cs_mod,
+            entry_point: "main",
+        });
+
+        ComputeKernel {
+            uniforms,
+            bindings,
+            pipeline,
+            size: output.size(),
+            workgroup_size,
+        }
+    }
+
+    /// Uploads new uniform values if they changed, they will be visible to the next submitted
+    /// dispatch.
+    pub fn write_uniforms(&mut self, queue: &wgpu::Queue, uniforms: U) {
+        self.uniforms.set(uniforms);
+        self.uniforms.flush(queue);
+    }
+
+    pub fn uniforms(&self) -> &UniformBuffer<U> {
+        &self.uniforms
+    }
+
+    pub fn uniforms_mut(&mut self) -> &mut UniformBuffer<U> {
+        &mut self.uniforms
+    }
+
+    /// Records a compute pass with enough workgroups to run the kernel once for every pixel of the
+    /// output texture.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let [x, y] = self.workgroup_size.workgroup_count(self.size);
+        let pass_desc = wgpu::ComputePassDescriptor {
+            label: Some("compute-kernel-pass"),
+        };
+        let mut cpass = encoder.begin_compute_pass(&pass_desc);
+        cpass.set_pipeline(&self.pipeline);
+        for (i, bind_group) in self.bindings.groups().iter().enumerate() {
+            cpass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        cpass.dispatch_workgroups(x, y, 1);
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn workgroup_size(&self) -> WorkgroupSize {
+        self.workgroup_size
+    }
+}
+
+/// Creates a texture that a `ComputeKernel` can write to and a render pipeline can sample from.
+pub fn create_storage_texture(device: &wgpu::Device, size: [u32; 2]) -> wgpu::Texture {
+    create_storage_texture_with_format(device, size, STORAGE_TEXTURE_FORMAT)
+}
+
+pub fn create_storage_texture_with_format(device: &wgpu::Device, size: [u32; 2], format: wgpu::TextureFormat) -> wgpu::Texture {
+    wgpu::TextureBuilder::new()
+        .size(size)
+        .format(format)
+        .usage(
+            wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+        )
+        .build(device)
+}
+
+/// The WGSL name of a storage texture format.
+///
+/// Panics for formats that can't be used as storage textures.
+pub fn storage_format_name(format: wgpu::TextureFormat) -> &'static str {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => "rgba8unorm",
+        wgpu::TextureFormat::Rgba8Snorm => "rgba8snorm",
+        wgpu::TextureFormat::Rgba16Float => "rgba16float",
+        wgpu::TextureFormat::Rgba32Float => "rgba32float",
+        wgpu::TextureFormat::R32Float => "r32float",
+        wgpu::TextureFormat::Rg32Float => "rg32float",
+        other => panic!("{:?} can't be used as a storage texture", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_the_workgroup_count_up() {
+        let workgroup_size = WorkgroupSize::default();
+        assert_eq!((workgroup_size.x(), workgroup_size.y()), (8, 8));
+        assert_eq!(workgroup_size.workgroup_count([17, 8]), [3, 1]);
+        assert_eq!(workgroup_size.workgroup_count([16, 16]), [2, 2]);
+        assert_eq!(workgroup_size.workgroup_count([1, 1]), [1, 1]);
+        assert_eq!(WorkgroupSize::new(16, 1).workgroup_count([1920, 1080]), [120, 1080]);
+    }
+
+    #[test]
+    #[should_panic(expected = "workgroup sizes must be at least 1")]
+    fn rejects_empty_workgroups() {
+        WorkgroupSize::new(0, 8);
+    }
+}
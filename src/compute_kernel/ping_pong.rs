@@ -0,0 +1,98 @@
+use nannou::wgpu;
+
+use crate::compute_kernel::{ComputeKernel, create_storage_texture_with_format, WorkgroupSize};
+use crate::uniforms::WgslUniform;
+
+/// Two textures that a kernel alternately reads from and writes to, so every step sees the
+/// result of the previous one. The base of feedback effects, cellular automata and simulations.
+///
+/// The kernel reads the previous state at `@binding(1)`, then `inputs` from `@binding(2)` on, and
+/// writes the new state right after them. See `ComputeKernel` for the rest of the layout.
+pub struct PingPong<U: WgslUniform> {
+    textures: [wgpu::Texture; 2],
+    /// `kernels[i]` reads `textures[i]` and writes the other one.
+    kernels: [ComputeKernel<U>; 2],
+    front: usize,
+}
+
+impl<U: WgslUniform> PingPong<U> {
+    pub fn new(
+        device: &wgpu::Device,
+        wgsl: &str,
+        workgroup_size: WorkgroupSize,
+        size: [u32; 2],
+        format: wgpu::TextureFormat,
+        inputs: &[&wgpu::Texture],
+        uniforms: U,
+    ) -> Self {
+        let textures = [
+            create_storage_texture_with_format(device, size, format),
+            create_storage_texture_with_format(device, size, format),
+        ];
+        let kernel = |read: usize| {
+            let mut kernel_inputs = vec![&textures[read]];
+            kernel_inputs.extend_from_slice(inputs);
+            ComputeKernel::with_inputs(device, wgsl, workgroup_size, &kernel_inputs, &textures[1 - read], uniforms)
+        };
+        let kernels = [kernel(0), kernel(1)];
+        PingPong {
+            textures,
+            kernels,
+            front: 0,
+        }
+    }
+
+    /// The texture holding the latest state, the one to draw.
+    pub fn front(&self) -> &wgpu::Texture {
+        &self.textures[self.front]
+    }
+
+    /// The texture the next step will write to.
+    pub fn back(&self) -> &wgpu::Texture {
+        &self.textures[1 - self.front]
+    }
+
+    /// Index of `front` in `textures`, to pick between bind groups created for both textures.
+    pub fn front_index(&self) -> usize {
+        self.front
+    }
+
+    pub fn textures(&self) -> &[wgpu::Texture; 2] {
+        &self.textures
+    }
+
+    /// Records a copy of `source` into the front texture, e.g. to start a simulation from an
+    /// image. `source` needs the same size and format and `wgpu::TextureUsages::COPY_SRC`.
+    pub fn seed(&mut self, encoder: &mut wgpu::CommandEncoder, source: &wgpu::Texture) {
+        encoder.copy_texture_to_texture(
+            source.as_image_copy(),
+            self.front().as_image_copy(),
+            self.front().extent(),
+        );
+    }
+
+    /// Records one iteration and swaps the textures.
+    pub fn step(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.kernels[self.front].dispatch(encoder);
+        self.front = 1 - self.front;
+    }
+
+    /// Records `count` iterations, each one reading the result of the previous.
+    pub fn steps(&mut self, encoder: &mut wgpu::CommandEncoder, count: u32) {
+        for _ in 0..count {
+            self.step(encoder);
+        }
+    }
+
+    /// Uploads new uniform values if they changed, they will be visible to the next submitted
+    /// steps.
+    pub fn write_uniforms(&mut self, queue: &wgpu::Queue, uniforms: U) {
+        for kernel in &mut self.kernels {
+            kernel.write_uniforms(queue, uniforms);
+        }
+    }
+
+    pub fn uniforms(&self) -> &U {
+        self.kernels[0].uniforms().get()
+    }
+}
@@ -0,0 +1,45 @@
+// Edge tangent flow from the smoothed structure tensor, the direction of least change.
+#include "uniforms/FlowDogUniforms.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: FlowDogUniforms;
+
+@group(0) @binding(1)
+var tensorTexture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var outTexture: texture_storage_2d<OUTPUT_FORMAT, write>;
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let tensor = textureLoad(tensorTexture, vec2<i32>(id.xy), 0);
+    let e = tensor.x;
+    let g = tensor.y;
+    let f = tensor.z;
+
+    // Eigenvalues of the tensor, the eigenvector of the largest one points along the gradient
+    let root = sqrt((e - g) * (e - g) + 4.0 * f * f);
+    let lambda1 = 0.5 * (e + g + root);
+    let lambda2 = 0.5 * (e + g - root);
+
+    var tangent = vec2(lambda1 - e, -f);
+    if (dot(tangent, tangent) > 1e-12) {
+        tangent = normalize(tangent);
+    } else {
+        // Flat areas have no preferred direction
+        tangent = vec2(0.0, 1.0);
+    }
+
+    // 0 for isotropic areas, 1 along strong straight edges
+    var anisotropy = 0.0;
+    if (lambda1 + lambda2 > 1e-12) {
+        anisotropy = (lambda1 - lambda2) / (lambda1 + lambda2);
+    }
+
+    textureStore(outTexture, id.xy, vec4(tangent, anisotropy, 1.0));
+}
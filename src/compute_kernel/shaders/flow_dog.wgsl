@@ -0,0 +1,82 @@
+// Last pass of the flow-based DoG, smooths both blurs along the flow and thresholds their
+// difference like the XDoG.
+#include "uniforms/FlowDogUniforms.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: FlowDogUniforms;
+
+@group(0) @binding(1)
+var sourceTexture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var flowTexture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var dogTexture: texture_2d<f32>;
+
+@group(0) @binding(4)
+var outTexture: texture_storage_2d<OUTPUT_FORMAT, write>;
+
+fn clamp_coords(position: vec2<f32>) -> vec2<i32> {
+    let max_coords = vec2<i32>(textureDimensions(flowTexture)) - 1;
+    return clamp(vec2<i32>(floor(position)), vec2(0), max_coords);
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    return exp(-(x * x) / (2.0 * sigma * sigma));
+}
+
+// Follows the flow in one direction, accumulating the weighted blurs and their weights
+fn integrate(start: vec2<f32>, start_tangent: vec2<f32>, steps: i32, sigma: f32) -> vec3<f32> {
+    var sum = vec3(0.0);
+    var position = start;
+    var tangent = start_tangent;
+    for (var i = 1; i <= steps; i = i + 1) {
+        var next = textureLoad(flowTexture, clamp_coords(position), 0).xy;
+        // The flow is a line field, keep going the same way
+        if (dot(next, tangent) < 0.0) {
+            next = -next;
+        }
+        tangent = next;
+        position += tangent * uniforms.step_size;
+
+        let weight = gaussian(f32(i) * uniforms.step_size, sigma);
+        let dog = textureLoad(dogTexture, clamp_coords(position), 0).xy;
+        sum += vec3(dog * weight, weight);
+    }
+    return sum;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let coords = vec2<i32>(id.xy);
+    let source = textureLoad(sourceTexture, coords, 0);
+    let tangent = textureLoad(flowTexture, coords, 0).xy;
+    let center = vec2<f32>(id.xy) + 0.5;
+
+    let sigma = max(uniforms.sigma_m, 0.01);
+    let steps = i32(ceil(2.0 * sigma / max(uniforms.step_size, 0.1)));
+
+    var sum = vec3(textureLoad(dogTexture, coords, 0).xy, 1.0);
+    sum += integrate(center, tangent, steps, sigma);
+    sum += integrate(center, -tangent, steps, sigma);
+    let blur = sum.x / sum.z;
+    let wide_blur = sum.y / sum.z;
+
+    // Sharpened difference of gaussians
+    let sharpened = (1.0 + uniforms.tau) * blur - uniforms.tau * wide_blur;
+
+    // Soft threshold, white above epsilon and a tanh ramp towards black below it
+    var edges = 1.0;
+    if (sharpened < uniforms.epsilon) {
+        edges = 1.0 + tanh(uniforms.phi * (sharpened - uniforms.epsilon));
+    }
+
+    let color = mix(vec3(edges), source.rgb * edges, uniforms.color_blend);
+    textureStore(outTexture, id.xy, vec4(color, 1.0));
+}
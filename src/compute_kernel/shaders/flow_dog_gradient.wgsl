@@ -0,0 +1,64 @@
+// Difference of gaussians in one dimension, across the edges (along the gradient of the flow).
+#include "common/color.wgsl"
+#include "uniforms/FlowDogUniforms.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: FlowDogUniforms;
+
+@group(0) @binding(1)
+var sourceTexture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var flowTexture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var outTexture: texture_storage_2d<OUTPUT_FORMAT, write>;
+
+fn lightness_at(coords: vec2<i32>) -> f32 {
+    let max_coords = vec2<i32>(textureDimensions(sourceTexture)) - 1;
+    let color = textureLoad(sourceTexture, clamp(coords, vec2(0), max_coords), 0);
+    return lightness(color);
+}
+
+// Bilinear lookup in pixel coordinates, samplers can't be bound to compute kernels
+fn lightness_bilinear(position: vec2<f32>) -> f32 {
+    let base = floor(position - 0.5);
+    let t = position - 0.5 - base;
+    let c = vec2<i32>(base);
+    let top = mix(lightness_at(c), lightness_at(c + vec2(1, 0)), t.x);
+    let bottom = mix(lightness_at(c + vec2(0, 1)), lightness_at(c + vec2(1, 1)), t.x);
+    return mix(top, bottom, t.y);
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    return exp(-(x * x) / (2.0 * sigma * sigma));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let tangent = textureLoad(flowTexture, vec2<i32>(id.xy), 0).xy;
+    let gradient = vec2(tangent.y, -tangent.x);
+    let center = vec2<f32>(id.xy) + 0.5;
+
+    let sigma = max(uniforms.sigma_e, 0.01);
+    let wide_sigma = sigma * uniforms.k;
+    let radius = i32(ceil(3.0 * wide_sigma));
+
+    var blur = vec2(0.0);
+    var wide_blur = vec2(0.0);
+    for (var i = -radius; i <= radius; i = i + 1) {
+        let x = f32(i);
+        let value = lightness_bilinear(center + gradient * x);
+        let weight = gaussian(x, sigma);
+        let wide_weight = gaussian(x, wide_sigma);
+        blur += vec2(value * weight, weight);
+        wide_blur += vec2(value * wide_weight, wide_weight);
+    }
+
+    textureStore(outTexture, id.xy, vec4(blur.x / blur.y, wide_blur.x / wide_blur.y, 0.0, 1.0));
+}
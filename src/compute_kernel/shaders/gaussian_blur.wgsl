@@ -0,0 +1,36 @@
+// One direction of a separable gaussian blur, run once horizontally and once vertically.
+#include "uniforms/BlurUniforms.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: BlurUniforms;
+
+@group(0) @binding(1)
+var inTexture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var outTexture: texture_storage_2d<OUTPUT_FORMAT, write>;
+
+fn weight(i: u32) -> f32 {
+    return uniforms.weights[i / 4u][i % 4u];
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let coords = vec2<i32>(id.xy);
+    let max_coords = vec2<i32>(textureDimensions(inTexture)) - 1;
+    let direction = vec2<i32>(uniforms.direction);
+
+    var color = textureLoad(inTexture, coords, 0) * weight(0u);
+    for (var i = 1u; i <= uniforms.radius; i = i + 1u) {
+        let offset = direction * i32(i);
+        color += textureLoad(inTexture, clamp(coords + offset, vec2(0), max_coords), 0) * weight(i);
+        color += textureLoad(inTexture, clamp(coords - offset, vec2(0), max_coords), 0) * weight(i);
+    }
+
+    textureStore(outTexture, id.xy, color);
+}
\ No newline at end of file
@@ -0,0 +1,43 @@
+// First pass of the flow-based DoG, the structure tensor of the lightness of the source image.
+#include "common/color.wgsl"
+#include "uniforms/FlowDogUniforms.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: FlowDogUniforms;
+
+@group(0) @binding(1)
+var inTexture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var outTexture: texture_storage_2d<OUTPUT_FORMAT, write>;
+
+fn lightness_at(coords: vec2<i32>) -> f32 {
+    let max_coords = vec2<i32>(textureDimensions(inTexture)) - 1;
+    let color = textureLoad(inTexture, clamp(coords, vec2(0), max_coords), 0);
+    return lightness(color);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let c = vec2<i32>(id.xy);
+    let top_left = lightness_at(c + vec2(-1, -1));
+    let top = lightness_at(c + vec2(0, -1));
+    let top_right = lightness_at(c + vec2(1, -1));
+    let left = lightness_at(c + vec2(-1, 0));
+    let right = lightness_at(c + vec2(1, 0));
+    let bottom_left = lightness_at(c + vec2(-1, 1));
+    let bottom = lightness_at(c + vec2(0, 1));
+    let bottom_right = lightness_at(c + vec2(1, 1));
+
+    // Sobel gradient
+    let gx = (top_right + 2.0 * right + bottom_right - top_left - 2.0 * left - bottom_left) / 4.0;
+    let gy = (bottom_left + 2.0 * bottom + bottom_right - top_left - 2.0 * top - top_right) / 4.0;
+
+    // (E, G, F) of the tensor [E F; F G], gets smoothed before computing the flow
+    textureStore(outTexture, id.xy, vec4(gx * gx, gy * gy, gx * gy, 1.0));
+}
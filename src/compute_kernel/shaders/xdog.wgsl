@@ -0,0 +1,44 @@
+// Extended difference of gaussians, combines two blurred versions of the source image.
+#include "uniforms/XdogUniforms.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: XdogUniforms;
+
+@group(0) @binding(1)
+var sourceTexture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var blurTexture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var wideBlurTexture: texture_2d<f32>;
+
+@group(0) @binding(4)
+var outTexture: texture_storage_2d<OUTPUT_FORMAT, write>;
+
+#include "common/color.wgsl"
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let coords = vec2<i32>(id.xy);
+    let source = textureLoad(sourceTexture, coords, 0);
+    let blur = lightness(textureLoad(blurTexture, coords, 0));
+    let wide_blur = lightness(textureLoad(wideBlurTexture, coords, 0));
+
+    // Sharpened difference of gaussians
+    let sharpened = (1.0 + uniforms.tau) * blur - uniforms.tau * wide_blur;
+
+    // Soft threshold, white above epsilon and a tanh ramp towards black below it
+    var edges = 1.0;
+    if (sharpened < uniforms.epsilon) {
+        edges = 1.0 + tanh(uniforms.phi * (sharpened - uniforms.epsilon));
+    }
+
+    let color = mix(vec3(edges), source.rgb * edges, uniforms.color_blend);
+    textureStore(outTexture, id.xy, vec4(color, 1.0));
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use nannou::wgpu;
+
+use crate::compute_kernel::{ComputeKernel, create_storage_texture_with_format, WorkgroupSize};
+use crate::compute_kernel::gaussian_blur::{GaussianBlur, INTERMEDIATE_TEXTURE_FORMAT};
+use crate::controls::UniformControls;
+use crate::interpolation::Interpolate;
+use crate::uniforms::ShaderUniform;
+
+/// Parameters of the extended difference of gaussians.
+///
+/// Missing fields take their default value when deserializing, so older presets keep loading.
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize, Interpolate, UniformControls)]
+#[serde(default)]
+pub struct XdogParams {
+    /// Standard deviation of the narrow blur, in pixels.
+    #[control(slider(0.1..=10.0))]
+    pub sigma: f32,
+    /// Ratio between the wide and the narrow blur.
+    #[control(slider(1.0..=5.0))]
+    pub k: f32,
+    /// Sharpening weight, `0` leaves the narrow blur as is.
+    #[control(slider(0.0..=100.0))]
+    pub tau: f32,
+    /// Threshold above which the result is white.
+    #[control(slider(0.0..=1.0))]
+    pub epsilon: f32,
+    /// Steepness of the transition to black below `epsilon`.
+    #[control(slider(0.0..=100.0))]
+    pub phi: f32,
+    /// `0` outputs the grey edge map, `1` multiplies it with the source colors.
+    #[control(slider(0.0..=1.0))]
+    pub color_blend: f32,
+}
+
+impl Default for XdogParams {
+    fn default() -> Self {
+        XdogParams {
+            sigma: 1.4,
+            k: 1.6,
+            tau: 20.0,
+            epsilon: 0.6,
+            phi: 10.0,
+            color_blend: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
+pub struct XdogUniforms {
+    pub tau: f32,
+    pub epsilon: f32,
+    pub phi: f32,
+    pub color_blend: f32,
+}
+
+impl From<&XdogParams> for XdogUniforms {
+    fn from(params: &XdogParams) -> Self {
+        XdogUniforms {
+            tau: params.tau,
+            epsilon: params.epsilon,
+            phi: params.phi,
+            color_blend: params.color_blend,
+        }
+    }
+}
+
+/// Ink-like stylization: two gaussian blurs of the source and a pass thresholding their
+/// sharpened difference.
+pub struct Xdog {
+    blur: GaussianBlur,
+    wide_blur: GaussianBlur,
+    combine: ComputeKernel<XdogUniforms>,
+    params: XdogParams,
+}
+
+impl Xdog {
+    /// `output` must be a storage texture of the same size as `input`.
+    pub fn new(device: &wgpu::Device, input: &wgpu::Texture, output: &wgpu::Texture, params: XdogParams) -> Self {
+        let blurred = create_storage_texture_with_format(device, input.size(), INTERMEDIATE_TEXTURE_FORMAT);
+        let wide_blurred = create_storage_texture_with_format(device, input.size(), INTERMEDIATE_TEXTURE_FORMAT);
+        let blur = GaussianBlur::new(device, input, &blurred, params.sigma);
+        let wide_blur = GaussianBlur::new(device, input, &wide_blurred, params.sigma * params.k);
+        let combine = ComputeKernel::with_inputs(
+            device,
+            include_str!("shaders/xdog.wgsl"),
+            WorkgroupSize::default(),
+            &[input, &blurred, &wide_blurred],
+            output,
+            XdogUniforms::from(&params),
+        );
+        Xdog {
+            blur,
+            wide_blur,
+            combine,
+            params,
+        }
+    }
+
+    pub fn params(&self) -> &XdogParams {
+        &self.params
+    }
+
+    /// Updates the parameters, only what changed is uploaded to the GPU.
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: XdogParams) {
+        if params.sigma != self.params.sigma || params.k != self.params.k {
+            self.blur.set_sigma(queue, params.sigma);
+            self.wide_blur.set_sigma(queue, params.sigma * params.k);
+        }
+        self.combine.write_uniforms(queue, XdogUniforms::from(&params));
+        self.params = params;
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.blur.dispatch(encoder);
+        self.wide_blur.dispatch(encoder);
+        self.combine.dispatch(encoder);
+    }
+}
@@ -0,0 +1,107 @@
+use std::ops::RangeInclusive;
+
+pub use nannou_egui::egui;
+use nannou_egui::egui::emath::Numeric;
+
+use crate::uniforms::{UniformBuffer, WgslUniform};
+
+pub use shader_uniform_derive::UniformControls;
+
+/// A struct, usually uniforms, that can show its fields as egui widgets.
+///
+/// Usually derived, each field with a `#[control(...)]` attribute gets a widget:
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform, UniformControls)]
+/// pub struct Uniforms {
+///     #[control(drag, speed = 0.1)]
+///     offset: [f32; 2],
+///     #[control(slider(1.0..=20.0))]
+///     accentuate: f32,
+///     #[control(slider(0.1..=100.0), logarithmic, label = "Edge width")]
+///     width: f32,
+///     #[control(color)]
+///     color: [f32; 3],
+///     #[control(toggle)]
+///     invert: u32,
+///     // Set from code, no widget
+///     time: f32,
+///     _padding: [f32; 3],
+/// }
+/// ```
+///
+/// Labels default to the field name in sentence case. A `UniformBuffer` of such a struct shows
+/// the same controls and uploads the new values on its next `flush`.
+pub trait UniformControls {
+    /// Shows a widget per annotated field, returns whether any of them changed a value.
+    fn controls(&mut self, ui: &mut egui::Ui) -> bool;
+}
+
+impl<T: WgslUniform + UniformControls> UniformControls for UniformBuffer<T> {
+    fn controls(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        self.update(|value| changed = value.controls(ui));
+        changed
+    }
+}
+
+pub fn slider<N: Numeric>(ui: &mut egui::Ui, label: &str, value: &mut N, range: RangeInclusive<N>, logarithmic: bool) -> bool {
+    ui.label(format!("{}:", label));
+    ui.add(egui::Slider::new(value, range).logarithmic(logarithmic)).changed()
+}
+
+pub fn drag<N: Numeric>(ui: &mut egui::Ui, label: &str, value: &mut N, speed: f64) -> bool {
+    ui.horizontal(|ui| {
+        let changed = ui.add(egui::DragValue::new(value).speed(speed)).changed();
+        ui.label(label);
+        changed
+    })
+    .inner
+}
+
+/// Two drag values side by side, for `x` and `y`.
+pub fn drag_vec2(ui: &mut egui::Ui, label: &str, value: &mut [f32; 2], speed: f64) -> bool {
+    ui.horizontal(|ui| {
+        let [x, y] = value;
+        let x_changed = ui.add(egui::DragValue::new(x).speed(speed).prefix("x: ")).changed();
+        let y_changed = ui.add(egui::DragValue::new(y).speed(speed).prefix("y: ")).changed();
+        ui.label(label);
+        x_changed || y_changed
+    })
+    .inner
+}
+
+/// `value` is linear RGB, as used in shaders.
+pub fn color_rgb(ui: &mut egui::Ui, label: &str, value: &mut [f32; 3]) -> bool {
+    ui.horizontal(|ui| {
+        let changed = ui.color_edit_button_rgb(value).changed();
+        ui.label(label);
+        changed
+    })
+    .inner
+}
+
+/// Edits a color with a straight (not premultiplied) alpha.
+pub fn color_rgba(ui: &mut egui::Ui, label: &str, value: &mut [f32; 4]) -> bool {
+    ui.horizontal(|ui| {
+        let changed = ui.color_edit_button_rgba_unmultiplied(value).changed();
+        ui.label(label);
+        changed
+    })
+    .inner
+}
+
+pub fn toggle(ui: &mut egui::Ui, label: &str, value: &mut bool) -> bool {
+    ui.checkbox(value, label).changed()
+}
+
+/// WGSL has no `bool` in uniforms, the flag is stored as `0` or `1`.
+pub fn toggle_u32(ui: &mut egui::Ui, label: &str, value: &mut u32) -> bool {
+    let mut checked = *value != 0;
+    let changed = toggle(ui, label, &mut checked);
+    if changed {
+        *value = checked as u32;
+    }
+    changed
+}
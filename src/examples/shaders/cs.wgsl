@@ -1,71 +1,38 @@
-struct Uniforms {
-    time: f32,
-    accentuate: f32,
-};
+#include "common/random.wgsl"
+#include "uniforms/DogUniforms.wgsl"
 
 @group(0) @binding(0)
-var<uniform> uniforms: Uniforms;
+var<uniform> uniforms: DogUniforms;
 
+// The source blurred with `DOG_SIGMA` and `DOG_WIDE_SIGMA`, see `compute_kernel::dog`
 @group(0) @binding(1)
-var inTexture: texture_2d<f32>;
+var narrowTexture: texture_2d<f32>;
 
 @group(0) @binding(2)
+var wideTexture: texture_2d<f32>;
+
+@group(0) @binding(3)
 var outTexture: texture_storage_2d<rgba8unorm, write>;
 
-@compute @workgroup_size(1, 1, 1)
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
 fn main(@builtin(global_invocation_id) id: vec3<u32>) {
-    var GAUSSIAN_BLUR_KERNEL: array<f32, 25> = array<f32, 25>(
-        2., 4., 5., 4., 2.,
-        4., 9., 12., 9., 4.,
-        5., 12., 15., 12., 5.,
-        4., 9., 12., 9., 4.,
-        2., 4., 5., 4., 2.,
-    );
-
-    var GAUSSIAN_BLUR_KERNEL_TYPE_2: array<f32, 25> = array<f32, 25>(
-        1.,   4.,  6.,  4.,  1., 
-        4.,  16., 24., 16.,  4., 
-        6.,  24., 36., 24.,  6.,
-        4.,  16., 24., 16.,  4.,
-        1.,   4.,  6.,  4.,  1.,
-    );
-
-
-    var GAUSSIAN_BLUR_STEPS: array<vec2<f32>, 25>  = array<vec2<f32>, 25>(
-        vec2(-2., -2.), vec2(-1., -2.), vec2(0., -2.), vec2(1., -2.), vec2(2., -2.),
-        vec2(-2., -1.), vec2(-1., -1.), vec2(0., -1.), vec2(1., -1.), vec2(2., -1.),
-        vec2(-2., 0.0), vec2(-1., 0.0), vec2(0., 0.0), vec2(1., 0.0), vec2(2., 0.0),
-        vec2(-2., 1.0), vec2(-1., 1.0), vec2(0., 1.0), vec2(1., 1.0), vec2(2., 1.0),
-        vec2(-2., 2.0), vec2(-1., 2.0), vec2(0., 2.0), vec2(1., 2.0), vec2(2., 2.0)
-    );
-
-    var colorA = 0.0;
-    var accumA = 0.0;
-
-    var colorB = 0.0;
-    var accumB = 0.0;
+    // The last row and column of workgroups can fall partially outside of the image.
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
 
     let lum = vec4(0.375, 0.5, 0.125, 0.);
+    let coords = vec2<i32>(id.xy);
+    let narrow = dot(textureLoad(narrowTexture, coords, 0), lum);
+    let wide = dot(textureLoad(wideTexture, coords, 0), lum);
 
-    // TODO: Use two passes: horizontal and vertical instead of this
-    for (var i = 0u; i < 25u; i = i + 1u) {
-        let pixel = textureLoad(inTexture, vec2<u32>(vec2<f32>(id.xy) + GAUSSIAN_BLUR_STEPS[i]), 0);
-
-        let cA = pixel * GAUSSIAN_BLUR_KERNEL[i];
-        colorA += dot(cA, lum);
-        accumA += GAUSSIAN_BLUR_KERNEL[i];
-
-        let cB = pixel * GAUSSIAN_BLUR_KERNEL_TYPE_2[i];
-        colorB += dot(cB, lum);
-        accumB += GAUSSIAN_BLUR_KERNEL_TYPE_2[i];
-    }
-
-    let gA =  colorA / accumA;
-    let gB =  colorB / accumB;
-    let diff = gB - gA;
+    let diff = narrow - wide;
     let distance = diff * uniforms.accentuate;
+    let edges = select(distance, 1.0 - distance, uniforms.invert != 0u)
+        + (random_at(id.xy, uniforms.seed) - 0.5) * uniforms.grain;
 
-    textureStore(outTexture, id.xy,  vec4(vec3(distance), 1.0));
+    textureStore(outTexture, id.xy,  vec4(edges * uniforms.color, 1.0));
 
     return;
-}
\ No newline at end of file
+}
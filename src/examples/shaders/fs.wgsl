@@ -2,12 +2,10 @@ struct FragmentOutput {
     @location(0) f_color: vec4<f32>,
 };
 
-struct ConvolutionUniform {
-    convolution: mat4x4<f32>,
-};
+#include "uniforms/ConvolutionUniform.wgsl"
 
 @group(1) @binding(0)
-var<uniform> convolution_matrix: ConvolutionUniform;
+var<uniform> convolution: ConvolutionUniform;
 @group(0) @binding(0)
 var tex: texture_2d<f32>;
 @group(0) @binding(1)
@@ -15,12 +13,21 @@ var tex_sampler: sampler;
 
 @fragment
 fn main(@location(0) tex_coords: vec2<f32>) -> FragmentOutput {
-
-    let w = convolution_matrix.convolution;
-
-    let out_color: vec4<f32> = textureSample(tex, tex_sampler, tex_coords);
-
-
+    let size = i32(convolution.size);
+    let half_size = size / 2;
+
+    var sum = vec3<f32>(0.0);
+    for (var y = 0; y < size; y = y + 1) {
+        for (var x = 0; x < size; x = x + 1) {
+            let i = y * size + x;
+            let weight = convolution.weights[i / 4][i % 4];
+            let offset = vec2<f32>(f32(x - half_size), f32(y - half_size)) * convolution.texel_size;
+            sum += textureSampleLevel(tex, tex_sampler, tex_coords + offset, 0.0).rgb * weight;
+        }
+    }
+
+    let alpha = textureSampleLevel(tex, tex_sampler, tex_coords, 0.0).a;
+    let out_color = vec4<f32>(sum / convolution.divisor + convolution.bias, alpha);
 
     return FragmentOutput(out_color);
 }
\ No newline at end of file
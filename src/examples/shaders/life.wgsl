@@ -0,0 +1,49 @@
+// One generation of a life-like cellular automaton, dark pixels are alive.
+struct LifeUniforms {
+    // Bit n is set when a dead cell with n live neighbours is born
+    birth: u32,
+    // Bit n is set when a live cell with n live neighbours survives
+    survival: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: LifeUniforms;
+
+@group(0) @binding(1)
+var previousState: texture_2d<f32>;
+
+@group(0) @binding(2)
+var outTexture: texture_storage_2d<rgba8unorm, write>;
+
+fn is_alive(coords: vec2<i32>) -> bool {
+    // The world wraps around the edges
+    let dimensions = vec2<i32>(textureDimensions(previousState));
+    let wrapped = (coords + dimensions) % dimensions;
+    return textureLoad(previousState, wrapped, 0).r < 0.5;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dimensions = textureDimensions(outTexture);
+    if (id.x >= dimensions.x || id.y >= dimensions.y) {
+        return;
+    }
+
+    let coords = vec2<i32>(id.xy);
+    var neighbours = 0u;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            if ((x != 0 || y != 0) && is_alive(coords + vec2(x, y))) {
+                neighbours += 1u;
+            }
+        }
+    }
+
+    var rule = uniforms.birth;
+    if (is_alive(coords)) {
+        rule = uniforms.survival;
+    }
+    let alive = (rule & (1u << neighbours)) != 0u;
+
+    textureStore(outTexture, id.xy, vec4(vec3(select(1.0, 0.0, alive)), 1.0));
+}
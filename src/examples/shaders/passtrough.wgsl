@@ -2,10 +2,6 @@ struct FragmentOutput {
     @location(0) f_color: vec4<f32>,
 };
 
-struct ConvolutionUniform {
-    convolution: mat4x4<f32>,
-};
-
 @group(0) @binding(0)
 var tex: texture_2d<f32>;
 @group(0) @binding(1)
@@ -14,5 +10,7 @@ var tex_sampler: sampler;
 @fragment
 fn main(@location(0) tex_coords: vec2<f32>) -> FragmentOutput {
     let out_color: vec4<f32> = textureSample(tex, tex_sampler, tex_coords);
-    return FragmentOutput(out_color);
+    // Black around the image when it doesn't cover the window
+    let inside = all(tex_coords >= vec2<f32>(0.0)) && all(tex_coords <= vec2<f32>(1.0));
+    return FragmentOutput(select(vec4<f32>(0.0, 0.0, 0.0, 1.0), out_color, inside));
 }
\ No newline at end of file
@@ -1,6 +1,16 @@
 use nannou::prelude::*;
 use nannou_egui::{Egui, egui};
 
+use lib::controls::UniformControls;
+use lib::interpolation::{Interpolate, Transition};
+use lib::presets::{Preset, PresetLibrary, PresetMorph};
+use lib::random::SketchRng;
+use lib::viewport::{egui_captures_pointer, Viewport};
+
+const PRESETS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/presets/simple_gui");
+/// The only effect of this sketch, as named in its presets.
+const EFFECT: &str = "ellipse";
+
 fn main() {
     nannou::app(model)
         .update(update)
@@ -9,17 +19,32 @@ fn main() {
 
 struct Model {
     texture: wgpu::Texture,
-    surface_size: [u32; 2],
+    /// Where the texture is drawn, scroll to zoom and drag to pan.
+    viewport: Viewport,
     egui: Egui,
     settings: Settings,
+    presets: PresetLibrary,
+    morph: PresetMorph<Settings>,
+    /// Seconds taken to go to a preset.
+    glide: f32,
+    transition: Option<Transition<Settings>>,
 }
 
+#[derive(Clone, UniformControls, Interpolate, serde::Serialize, serde::Deserialize)]
 struct Settings {
+    #[control(slider(1..=40))]
     resolution: u32,
+    #[control(slider(0.0..=1000.0))]
     scale: f32,
+    #[control(slider(0.0..=360.0))]
+    #[interpolate(degrees)]
     rotation: f32,
-    color: Srgb<u8>,
-    position: Vec2,
+    #[control(color)]
+    #[interpolate(color)]
+    color: [f32; 3],
+    #[control(drag)]
+    position: [f32; 2],
+    rng: SketchRng,
 }
 
 fn model(_app: &App) -> Model {
@@ -38,52 +63,86 @@ fn model(_app: &App) -> Model {
     let assets = _app.assets_path().unwrap();
     let img_path = assets.join("imagen.jpg");
     let texture = wgpu::Texture::from_path(_app, img_path).unwrap();
-    Model {
-        texture,
-        surface_size,
-        egui,
-        settings: Settings {
+    // Started with `--preset <path>`
+    let settings = match Preset::from_args() {
+        Some(preset) => preset.unwrap_or_else(|err| panic!("{}", err)).params,
+        None => Settings {
             resolution: 10,
             scale: 200.0,
             rotation: 0.0,
-            color: WHITE,
-            position: vec2(0.0, 0.0),
+            color: [1.0; 3],
+            position: [0.0, 0.0],
+            rng: SketchRng::from_entropy(),
         },
+    };
+    Model {
+        texture,
+        viewport: Viewport::default(),
+        egui,
+        settings,
+        presets: PresetLibrary::new(PRESETS_DIR),
+        morph: PresetMorph::new(),
+        glide: 1.0,
+        transition: None,
     }
 }
 
 fn update(_app: &App, _model: &mut Model, _update: Update) {
+    if let Some(transition) = &_model.transition {
+        _model.settings = transition.value(_app.time);
+        if transition.is_finished(_app.time) {
+            _model.transition = None;
+        }
+    }
+
     let egui = &mut _model.egui;
     let settings = &mut _model.settings;
+    let presets = &mut _model.presets;
+    let morph = &mut _model.morph;
+    let glide = &mut _model.glide;
+    let transition = &mut _model.transition;
+    let viewport = &mut _model.viewport;
 
     egui.set_elapsed_time(_update.since_start);
     let ctx = egui.begin_frame();
 
     egui::Window::new("Settings").show(&ctx, |ui| {
-        // Resolution slider
-        ui.label("Resolution:");
-        ui.add(egui::Slider::new(&mut settings.resolution, 1..=40));
-
-        // Scale slider
-        ui.label("Scale:");
-        ui.add(egui::Slider::new(&mut settings.scale, 0.0..=1000.0));
-
-        // Rotation slider
-        ui.label("Rotation:");
-        ui.add(egui::Slider::new(&mut settings.rotation, 0.0..=360.0));
+        settings.controls(ui);
 
         // Random color button
         let clicked = ui.button("Random color").clicked();
 
         if clicked {
-            settings.color = rgb(random(), random(), random());
+            settings.color = settings.rng.color();
         }
+        settings.rng.ui(ui);
+
+        ui.separator();
+        ui.collapsing("View", |ui| {
+            viewport.ui(ui);
+        });
+        ui.collapsing("Presets", |ui| {
+            if let Some(preset) = presets.ui(ui, &Preset::new(EFFECT, settings.clone())) {
+                *transition = Some(Transition::new(settings.clone(), preset.params, _app.time, *glide));
+            }
+            ui.label("Glide (seconds):");
+            ui.add(egui::Slider::new(glide, 0.0..=10.0));
+        });
+        ui.collapsing("Morph", |ui| {
+            if let Some(blend) = morph.ui(ui, presets) {
+                *settings = blend;
+                *transition = None;
+            }
+        });
     });
 }
 
 fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
     // Let egui handle things like keyboard and mouse input.
     model.egui.handle_raw_event(event);
+    let (width, height) = _app.main_window().inner_size_pixels();
+    let captured = egui_captures_pointer(model.egui.ctx());
+    model.viewport.handle_event(event, model.texture.size(), [width as f32, height as f32], captured);
 }
 
 fn view(_app: &App, _model: &Model, frame: Frame) {
@@ -91,26 +150,17 @@ fn view(_app: &App, _model: &Model, frame: Frame) {
     let draw = _app.draw();
     draw.background().color(BLACK);
 
-    let size = _model.texture.size();
-
-    let scale_to_fit: [f32; 2] = if size[0] > size[1] {
-        let new_ratio: f32 = _model.surface_size[0].to_f32().unwrap() / size[0].to_f32().unwrap();
-        let new_height: f32 = new_ratio * size[1].to_f32().unwrap();
-        [_model.surface_size[0].to_f32().unwrap(), new_height]
-    } else {
-        let new_ratio: f32 = _model.surface_size[1].to_f32().unwrap() / size[1].to_f32().unwrap();
-        let new_width: f32 = new_ratio * size[1].to_f32().unwrap();
-        [_model.surface_size[1].to_f32().unwrap(), new_width]
-    };
-
-    draw.texture(&_model.texture).wh(scale_to_fit.into());
+    let window = _app.window(frame.window_id()).unwrap();
+    let rect = _model.viewport.draw_rect(_model.texture.size(), &window);
+    draw.texture(&_model.texture).xy(rect.xy()).wh(rect.wh());
 
     let settings = &_model.settings;
     let rotation_radians = deg_to_rad(settings.rotation);
+    let [r, g, b] = settings.color;
     draw.ellipse()
         .resolution(settings.resolution as f32)
-        .xy(settings.position)
-        .color(settings.color)
+        .xy(settings.position.into())
+        .color(lin_srgb(r, g, b))
         .rotate(-rotation_radians)
         .radius(settings.scale);
 
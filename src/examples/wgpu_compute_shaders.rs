@@ -4,60 +4,153 @@
 //! oscillators. The oscillator amplitudes are then laid out across the screen using rectangles
 //! with a gray value equal to the amplitude. Real-time interaction is demonstrated by providing
 //! access to time, frequency (mouse `x`) and the number of oscillators via uniform data.
+//!
+//! Run with `HOT_RELOAD=1` to reload `cs.wgsl`, `tiled_vs.wgsl` and `passtrough.wgsl` when they
+//! are saved, and with `--preset <path>` to start from a preset saved from the settings window.
+//!
+//! The "Record" section renders an animation offscreen on a fixed clock, see `lib::recording`.
+//! Scroll to zoom and drag to pan, the "View" section sets how the result fits the window. The
+//! "Inspect" section shows the source and processed pixels under the cursor.
 
 use std::cell::Ref;
 
 use nannou::image;
-use nannou::image::DynamicImage;
 use nannou::prelude::*;
 use nannou::wgpu::{BufferInitDescriptor, Device};
 use nannou_egui::{Egui, egui};
-use nannou_egui::egui_wgpu::wgpu::TextureView;
 
+use lib::canvas::tiled::TileUniform;
+use lib::compute_kernel::{create_storage_texture, STORAGE_TEXTURE_FORMAT, WorkgroupSize};
+use lib::compute_kernel::dog::{Dog, DogUniforms};
+use lib::compute_kernel::flow_dog::{FlowDog, FlowDogParams};
+use lib::compute_kernel::ping_pong::PingPong;
+use lib::compute_kernel::xdog::{Xdog, XdogParams};
+use lib::hot_reload;
+use lib::hot_reload::HotReload;
+use lib::inspector::Inspector;
+use lib::interpolation::{Interpolate, Transition};
+use lib::preprocessor;
+use lib::preprocessor::Preprocessor;
+use lib::presets::{Preset, PresetLibrary, PresetMorph};
+use lib::random::SketchRng;
+use lib::recording::{preset_path, Recorder, RecordingSettings};
+use lib::reflection::{BindResource, Bindings, ShaderReflection};
 use lib::shader_processing::model::{QUAD, Vert};
+use lib::shader_processing::offscreen::{create_render_target, read_texture, OffscreenError, OFFSCREEN_TEXTURE_FORMAT};
+use lib::shader_processing::pipeline::tiled_vertex_shader;
+use lib::controls::UniformControls;
+use lib::uniforms::{ShaderUniform, UniformBuffer};
+use lib::viewport::{egui_captures_pointer, Viewport};
 
 fn main() {
     nannou::app(model).update(update).run();
 }
 
+const CS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/shaders/cs.wgsl");
+const VS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader_processing/shaders/tiled_vs.wgsl");
+const FS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/shaders/passtrough.wgsl");
+const PRESETS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/presets/wgpu_compute_shaders");
+
 struct Model {
-    compute: Compute,
-    render: Render,
+    dog: HotReload<Dog>,
+    xdog: Xdog,
+    flow_dog: FlowDog,
+    life: PingPong<LifeUniforms>,
+    texture: wgpu::Texture,
+    storage_texture: wgpu::Texture,
+    render: HotReload<Render>,
+    viewport: Viewport,
+    inspector: Inspector,
+    /// Why the inspector was turned off, if reading the pixels back failed.
+    inspector_error: Option<String>,
     gui: Gui,
+    recording: Recording,
 }
 
 struct Gui {
     egui: Egui,
     settings: Settings,
+    /// Saved in presets by its name.
+    effect: Effect,
+    reseed_life: bool,
+    presets: PresetLibrary,
+    morph: PresetMorph<Settings>,
+    /// Seconds taken to go to a preset.
+    glide: f32,
+    transition: Option<Transition<Settings>>,
 }
 
+/// The parameters saved in presets.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Interpolate)]
 struct Settings {
-    resolution: u32,
-    scale: f32,
-    accentuate: f32,
-    color: Srgb<u8>,
-    position: Vec2,
+    uniforms: DogUniforms,
+    pre_blur: f32,
+    xdog: XdogParams,
+    flow_dog: FlowDogParams,
+    life_steps: u32,
+    rng: SketchRng,
 }
 
-struct Compute {
-    uniform_buffer: wgpu::Buffer,
-    bind_group: wgpu::BindGroup,
-    pipeline: wgpu::ComputePipeline,
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Effect {
+    Dog,
+    Xdog,
+    FlowDog,
+    Life,
+}
+
+impl Effect {
+    const ALL: [Effect; 4] = [Effect::Dog, Effect::Xdog, Effect::FlowDog, Effect::Life];
+
+    /// Same names as the effects of `batch_process`.
+    fn name(&self) -> &'static str {
+        match self {
+            Effect::Dog => "dog",
+            Effect::Xdog => "xdog",
+            Effect::FlowDog => "fdog",
+            Effect::Life => "life",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Effect> {
+        Effect::ALL.into_iter().find(|effect| effect.name() == name)
+    }
+}
+
+/// Renders frames offscreen on the fixed clock of a `Recorder`, see `record_frame`.
+struct Recording {
+    settings: RecordingSettings,
+    recorder: Option<Recorder>,
+    /// The render pipeline for the offscreen target, built when a recording starts.
+    render: Option<Render>,
+    /// The offscreen target every frame is rendered to, created when a recording starts.
+    target: Option<wgpu::Texture>,
+    /// How the last recording ended.
+    status: Option<String>,
 }
 
 struct Render {
-    pub bind_group: wgpu::BindGroup,
+    /// One for the storage texture, followed by one for each texture of the game of life.
+    pub bindings: Vec<Bindings>,
     pub render_pipeline: wgpu::RenderPipeline,
     pub vertex_buffer: wgpu::Buffer,
+    /// Where the result is drawn in the target, the whole of it unless set from a `Viewport`.
+    pub tile_uniform: UniformBuffer<TileUniform>,
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
-pub struct Uniforms {
-    time: f32,
-    accentuate: f32,
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
+pub struct LifeUniforms {
+    birth: u32,
+    survival: u32,
 }
 
+/// Conway's B3/S23 rules.
+const CONWAY: LifeUniforms = LifeUniforms {
+    birth: 1 << 3,
+    survival: (1 << 2) | (1 << 3),
+};
+
 fn model(app: &App) -> Model {
     let w_id = app.new_window()
         .size(1024, 1024)
@@ -74,140 +167,225 @@ fn model(app: &App) -> Model {
     let texture_path_buffer = app.assets_path().unwrap().join("imagen.jpg");
     let image = image::open(texture_path_buffer).unwrap();
     let texture = wgpu::Texture::from_image(&window, &image);
-    let texture_view = texture.view().build();
 
     // This texture will be the compute shader's output and the fragment shader's input,
     // allowing us to render the compute shader's result onto the Window.
-    let storage_texture = device.create_texture(&wgpu::TextureDescriptor {
-        label: None,
-        size: wgpu::Extent3d {
-            width: texture.width(),
-            height: texture.height(),
-            depth_or_array_layers: 1,
-        },
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8Unorm,
-        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-        view_formats: &[],
-    });
-    let storage_texture_view = storage_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-    let compute = build_compute_pipeline(app, &window, device, &texture_view, &storage_texture_view);
-    let render = build_render_pipeline(&window, device, &storage_texture_view);
+    let storage_texture = create_storage_texture(device, texture.size());
+
+    // Optional pre-blur followed by the DoG of `cs.wgsl`
+    let dog = Dog::new(
+        device,
+        include_str!("shaders/cs.wgsl"),
+        &texture,
+        &storage_texture,
+        DogUniforms::default(),
+    )
+    .unwrap();
+    let xdog = Xdog::new(device, &texture, &storage_texture, XdogParams::default());
+    let flow_dog = FlowDog::new(device, &texture, &storage_texture, FlowDogParams::default());
+    // The game of life starts from the XDoG lines, see `update`.
+    let life = PingPong::new(
+        device,
+        include_str!("shaders/life.wgsl"),
+        WorkgroupSize::default(),
+        texture.size(),
+        STORAGE_TEXTURE_FORMAT,
+        &[],
+        CONWAY,
+    );
+    let render = build_render_pipeline(
+        device,
+        Frame::TEXTURE_FORMAT,
+        window.msaa_samples(),
+        &tiled_vertex_shader(),
+        include_str!("shaders/passtrough.wgsl"),
+        &render_texture_views(&storage_texture, &life),
+    );
     let gui = build_gui_state(&window);
     
     Model {
-        compute,
-        render,
+        dog: HotReload::watch_if_enabled(dog, &[CS_PATH]).with_preprocessor(
+            Preprocessor::new()
+                .with_uniform::<DogUniforms>()
+                .with_search_path(preprocessor::INCLUDE_DIR),
+        ),
+        xdog,
+        flow_dog,
+        life,
+        texture,
+        storage_texture,
+        render: HotReload::watch_if_enabled(render, &[VS_PATH, FS_PATH]).with_preprocessor(
+            Preprocessor::new()
+                .with_uniform::<TileUniform>()
+                .with_search_path(preprocessor::INCLUDE_DIR),
+        ),
+        viewport: Viewport::default(),
+        inspector: Inspector::default(),
+        inspector_error: None,
         gui,
+        recording: Recording {
+            settings: RecordingSettings::default(),
+            recorder: None,
+            render: None,
+            target: None,
+            status: None,
+        },
     }
 }
 
 fn build_gui_state(window: &Ref<Window>) -> Gui {
-    let egui = Egui::from_window(&window);
-    let gui = Gui {
+    let egui = Egui::from_window(window);
+    let mut gui = Gui {
         egui,
         settings: Settings {
-            resolution: 10,
-            scale: 200.0,
-            accentuate: 0.0,
-            color: WHITE,
-            position: vec2(0.0, 0.0),
-        }
+            uniforms: DogUniforms::default(),
+            pre_blur: 0.0,
+            xdog: XdogParams::default(),
+            flow_dog: FlowDogParams::default(),
+            life_steps: 1,
+            rng: SketchRng::from_entropy(),
+        },
+        effect: Effect::Xdog,
+        reseed_life: true,
+        presets: PresetLibrary::new(PRESETS_DIR),
+        morph: PresetMorph::new(),
+        glide: 1.0,
+        transition: None,
     };
+    if let Some(preset) = Preset::from_args() {
+        gui.apply_preset(preset.unwrap_or_else(|err| panic!("{}", err)), 0.0, 0.0);
+    }
     gui
 }
 
-fn build_compute_pipeline(app: &App, window: &Ref<Window>, device: &Device, texture_view: &TextureView, storage_texture_view: &TextureView) -> Compute {
-// Create the compute shader module.
-    let cs_desc = wgpu::include_wgsl!("shaders/cs.wgsl");
-    let cs_mod = device.create_shader_module(cs_desc);
-    
-    // Create the buffer that will store time.
-    let uniforms = create_uniforms(app.time, 1f32);
-    let uniforms_bytes = uniforms_as_bytes(&uniforms);
-    let usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
-    let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        label: Some("uniform-buffer"),
-        contents: uniforms_bytes,
-        usage,
-    });
+impl Gui {
+    /// Glides to the parameters of the preset, keeps the current effect if the preset names an
+    /// unknown one.
+    fn apply_preset(&mut self, preset: Preset<Settings>, time: f32, duration: f32) {
+        self.effect = Effect::from_name(&preset.effect).unwrap_or(self.effect);
+        self.reseed_life = true;
+        let transition = Transition::new(self.settings.clone(), preset.params, time, duration);
+        self.settings = transition.value(time);
+        self.transition = Some(transition);
+    }
 
-    // Create the bind group and pipeline.
-    let uniform_dynamic = false;
-    let bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
-        .uniform_buffer(wgpu::ShaderStages::COMPUTE, uniform_dynamic)
-        .texture(
-            wgpu::ShaderStages::COMPUTE,
-            false,
-            wgpu::TextureViewDimension::D2,
-            wgpu::TextureSampleType::Float { filterable: true },
-        )
-        .storage_texture(
-            wgpu::ShaderStages::COMPUTE,
-            wgpu::TextureFormat::Rgba8Unorm,
-            wgpu::TextureViewDimension::D2,
-            wgpu::StorageTextureAccess::WriteOnly,
-        )
-        .build(device);
+    /// Moves the running transition, if any, to the given time.
+    fn advance_transition(&mut self, time: f32) {
+        if let Some(transition) = &self.transition {
+            self.settings = transition.value(time);
+            if transition.is_finished(time) {
+                self.transition = None;
+            }
+        }
+    }
+}
 
-    let bind_group = wgpu::BindGroupBuilder::new()
-        .buffer::<Uniforms>(&uniform_buffer, 0..1)
-        .texture_view(&texture_view) // <- Input texture
-        .texture_view(&storage_texture_view)// <- Output texture
-        .build(device, &bind_group_layout);
+impl Recording {
+    /// The fixed clock while recording, the real one otherwise.
+    fn time(&self, real_time: f32) -> f32 {
+        self.recorder.as_ref().map_or(real_time, Recorder::time)
+    }
 
-    let pipeline_layout = create_pipeline_layout(device, &bind_group_layout);
-    let pipeline = create_compute_pipeline(device, &pipeline_layout, &cs_mod);
+    /// Saves the preset next to the frames, so that the recording can be rendered again.
+    fn start(&mut self, device: &Device, preset: &Preset<Settings>, render: Render) {
+        self.status = None;
+        let recorder = match Recorder::start(self.settings.clone()) {
+            Ok(recorder) => recorder,
+            Err(err) => {
+                self.status = Some(err.to_string());
+                return;
+            }
+        };
+        if let Err(err) = preset.save(preset_path(&self.settings.directory)) {
+            self.status = Some(err.to_string());
+            return;
+        }
+        self.target = Some(create_render_target(device, self.settings.size));
+        self.recorder = Some(recorder);
+        self.render = Some(render);
+    }
 
-    let compute = Compute {
-        uniform_buffer,
-        bind_group,
-        pipeline,
-    };
-    compute
+    /// Writes a frame rendered by `record_frame`, stops after the last one or on failure.
+    fn write_frame(&mut self, image: Result<image::RgbaImage, OffscreenError>) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let written = image
+            .map_err(|err| err.to_string())
+            .and_then(|image| recorder.write_frame(&image).map_err(|err| err.to_string()));
+        match written {
+            Err(err) => {
+                self.stop();
+                self.status = Some(err);
+            }
+            Ok(()) if recorder.is_finished() => self.stop(),
+            Ok(()) => {}
+        }
+    }
+
+    fn stop(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        let frames = recorder.frame();
+        let directory = recorder.settings().directory.clone();
+        self.render = None;
+        self.target = None;
+        self.status = Some(match recorder.finish() {
+            Ok(()) => format!("Recorded {} frames to {}", frames, directory.display()),
+            Err(err) => err.to_string(),
+        });
+    }
 }
 
-fn build_render_pipeline(window: &Ref<Window>, device: &Device, storage_texture_view: &TextureView) -> Render {
-    let format = Frame::TEXTURE_FORMAT;
-    let msaa_samples = window.msaa_samples();
-    let vs_desc = wgpu::include_wgsl!("shaders/vs.wgsl");
-    let fs_desc = wgpu::include_wgsl!("shaders/passtrough.wgsl");
+/// A view of the storage texture, followed by one for each texture of the game of life.
+fn render_texture_views(storage_texture: &wgpu::Texture, life: &PingPong<LifeUniforms>) -> Vec<wgpu::TextureView> {
+    let life_views = life.textures().iter().map(|texture| texture.view().build());
+    std::iter::once(storage_texture.view().build()).chain(life_views).collect()
+}
+
+fn build_render_pipeline(
+    device: &Device,
+    format: wgpu::TextureFormat,
+    msaa_samples: u32,
+    vs_source: &str,
+    fs_source: &str,
+    texture_views: &[wgpu::TextureView],
+) -> Render {
+    let vs_desc = wgpu::ShaderModuleDescriptor {
+        label: Some("tiled_vs.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(vs_source.into()),
+    };
+    let fs_desc = wgpu::ShaderModuleDescriptor {
+        label: Some("passtrough.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(fs_source.into()),
+    };
 
     let vs_mod = device.create_shader_module(vs_desc);
     let fs_mod = device.create_shader_module(fs_desc);
 
     // Create the sampler for sampling from the source texture.
-    let sampler_desc = wgpu::SamplerBuilder::new().into_descriptor();
-    let sampler_filtering = wgpu::sampler_filtering(&sampler_desc);
-    let sampler = device.create_sampler(&sampler_desc);
-
-
-    let render_bind_group_layout =
-        wgpu::BindGroupLayoutBuilder::new()
-            .texture(
-                wgpu::ShaderStages::FRAGMENT,
-                false,
-                wgpu::TextureViewDimension::D2,
-                wgpu::TextureSampleType::Float { filterable: true },
-            )
-            .sampler(wgpu::ShaderStages::FRAGMENT, sampler_filtering)
-            .build(device);
-
-    let render_bind_group = wgpu::BindGroupBuilder::new()
-        .texture_view(&storage_texture_view)
-        .sampler(&sampler)
-        .build(device, &render_bind_group_layout);
-
-
-    let desc = wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[&render_bind_group_layout],
-        push_constant_ranges: &[],
-    };
-    let render_pipeline_layout = device.create_pipeline_layout(&desc);
+    let sampler = device.create_sampler(&wgpu::SamplerBuilder::new().into_descriptor());
+
+    let tile_uniform = UniformBuffer::new(device, "render-tile", TileUniform::FULL);
+
+    // One set of bindings per texture that can be shown, they all share the same layout
+    let vs_reflection = ShaderReflection::from_wgsl(vs_source).unwrap_or_else(|err| panic!("{}", err));
+    let reflection = ShaderReflection::from_wgsl(fs_source)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .merge(vs_reflection);
+    let render_bindings: Vec<_> = texture_views
+        .iter()
+        .map(|texture_view| {
+            let resources = [
+                ("tex", BindResource::TextureView(texture_view)),
+                ("tex_sampler", BindResource::Sampler(&sampler)),
+                ("tile", BindResource::Buffer(tile_uniform.buffer())),
+            ];
+            reflection.bind(device, &resources).unwrap_or_else(|err| panic!("{}", err))
+        })
+        .collect();
+    let render_pipeline_layout = render_bindings[0].pipeline_layout(device, "render");
 
     let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&render_pipeline_layout, &vs_mod)
         .fragment_shader(&fs_mod)
@@ -217,7 +395,7 @@ fn build_render_pipeline(window: &Ref<Window>, device: &Device, storage_texture_
         .primitive_topology(wgpu::PrimitiveTopology::TriangleStrip)
         .build(device);
 
-    let vertices_bytes = vertices_as_bytes(&QUAD[..]);
+    let vertices_bytes = bytemuck::cast_slice(&QUAD[..]);
     let usage = wgpu::BufferUsages::VERTEX;
     let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
         label: None,
@@ -225,46 +403,238 @@ fn build_render_pipeline(window: &Ref<Window>, device: &Device, storage_texture_
         usage,
     });
 
-    let render = Render {
-        bind_group: render_bind_group,
+    Render {
+        bindings: render_bindings,
         render_pipeline,
         vertex_buffer,
-    };
-    render
+        tile_uniform,
+    }
 }
 
 fn update(app: &App, model: &mut Model, _update: Update) {
-    let egui = &mut model.gui.egui;
-    let settings = &mut model.gui.settings;
+    reload_shaders(app, model);
+
+    let time = model.recording.time(app.time);
+    model.gui.advance_transition(time);
+    let gui = &mut model.gui;
+    let recording = &mut model.recording;
+    let viewport = &mut model.viewport;
+    let inspector = &mut model.inspector;
+    let inspector_error = &model.inspector_error;
+    let mut start_recording = false;
+    let egui = &mut gui.egui;
+    let settings = &mut gui.settings;
+    let mut loaded = None;
 
     egui.set_elapsed_time(_update.since_start);
     let ctx = egui.begin_frame();
 
     egui::Window::new("Settings").show(&ctx, |ui| {
-        // Resolution slider
-        ui.label("Resolution:");
-        ui.add(egui::Slider::new(&mut settings.resolution, 1..=40));
-
-        // Scale slider
-        ui.label("Scale:");
-        ui.add(egui::Slider::new(&mut settings.scale, 0.0..=1000.0));
+        // One widget per annotated field of the DoG uniforms
+        settings.uniforms.controls(ui);
 
-        // Rotation slider
-        ui.label("Accentuate:");
-        ui.add(egui::Slider::new(&mut settings.accentuate, 1.0..=20.0));
+        ui.label("Pre-blur:");
+        ui.add(egui::Slider::new(&mut settings.pre_blur, 0.0..=10.0));
 
         // Random color button
         let clicked = ui.button("Random color").clicked();
 
         if clicked {
-            settings.color = rgb(random(), random(), random());
+            settings.uniforms.color = settings.rng.color();
+        }
+        settings.rng.ui(ui);
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut gui.effect, Effect::Dog, "DoG");
+            ui.selectable_value(&mut gui.effect, Effect::Xdog, "XDoG");
+            ui.selectable_value(&mut gui.effect, Effect::FlowDog, "FDoG");
+            ui.selectable_value(&mut gui.effect, Effect::Life, "Life");
+        });
+
+        if gui.effect == Effect::Xdog {
+            settings.xdog.controls(ui);
+        }
+
+        if gui.effect == Effect::FlowDog {
+            settings.flow_dog.controls(ui);
         }
+
+        if gui.effect == Effect::Life {
+            ui.label("Generations per frame:");
+            ui.add(egui::Slider::new(&mut settings.life_steps, 0..=20));
+            if ui.button("Restart from XDoG").clicked() {
+                gui.reseed_life = true;
+            }
+        }
+
+        ui.separator();
+        ui.collapsing("View", |ui| {
+            viewport.ui(ui);
+        });
+        ui.collapsing("Inspect", |ui| {
+            inspector.ui(ui);
+            if let Some(err) = inspector_error {
+                ui.label(err);
+            }
+        });
+        ui.collapsing("Presets", |ui| {
+            let current = Preset::new(gui.effect.name(), settings.clone());
+            loaded = gui.presets.ui(ui, &current);
+            ui.label("Glide (seconds):");
+            ui.add(egui::Slider::new(&mut gui.glide, 0.0..=10.0));
+        });
+        ui.collapsing("Morph", |ui| {
+            if let Some(blend) = gui.morph.ui(ui, &gui.presets) {
+                *settings = blend;
+                gui.transition = None;
+            }
+        });
+        ui.collapsing("Record", |ui| match &recording.recorder {
+            Some(recorder) => {
+                let text = format!("Frame {} / {}", recorder.frame(), recorder.settings().frames);
+                ui.add(egui::ProgressBar::new(recorder.progress()).text(text));
+                if ui.button("Stop").clicked() {
+                    recording.stop();
+                }
+            }
+            None => {
+                recording.settings.ui(ui);
+                start_recording = ui.button("Record").clicked();
+                if let Some(status) = &recording.status {
+                    ui.label(status);
+                }
+            }
+        });
     });
+
+    let errors = model.dog.error().into_iter().chain(model.render.error());
+    hot_reload::show_errors(&ctx, errors);
+    // Not over the GUI, the pixels shown are the ones read in the last update
+    let pointer_captured = egui_captures_pointer(&ctx);
+    if !pointer_captured {
+        inspector.show(&ctx, viewport.cursor());
+    }
+    ctx.end();
+
+    if let Some(preset) = loaded {
+        gui.apply_preset(preset, time, gui.glide);
+    }
+    let window = app.main_window();
+    if start_recording {
+        // The recording starts from the current look, on a clock of its own.
+        gui.transition = None;
+        gui.reseed_life = true;
+        let render = build_render_pipeline(
+            window.device(),
+            OFFSCREEN_TEXTURE_FORMAT,
+            1,
+            &tiled_vertex_shader(),
+            include_str!("shaders/passtrough.wgsl"),
+            &render_texture_views(&model.storage_texture, &model.life),
+        );
+        model.recording.start(window.device(), &Preset::new(gui.effect.name(), gui.settings.clone()), render);
+    }
+    let time = model.recording.time(time);
+    let settings = &mut gui.settings;
+
+    let (width, height) = window.inner_size_pixels();
+    let rect = model.viewport.rect(model.storage_texture.size(), [width as f32, height as f32]);
+    let render = model.render.get_mut();
+    render.tile_uniform.set(TileUniform::for_rect(rect, [width, height]));
+    render.tile_uniform.flush(window.queue());
+
+    // Only uploaded to the GPU when the values change.
+    settings.uniforms.time = time;
+    settings.uniforms.seed = settings.rng.seed();
+    let dog = model.dog.get_mut();
+    dog.graph.write_uniforms(window.queue(), dog.edges, settings.uniforms);
+    dog.pre_blur.set_sigma(&mut dog.graph, window.queue(), settings.pre_blur);
+    model.xdog.set_params(window.queue(), settings.xdog);
+    model.flow_dog.set_params(window.queue(), settings.flow_dog);
+
+    // The simulation advances here rather than in `view`, as every step swaps its textures.
+    if gui.effect == Effect::Life {
+        let desc = wgpu::CommandEncoderDescriptor {
+            label: Some("game-of-life"),
+        };
+        let mut encoder = window.device().create_command_encoder(&desc);
+        if gui.reseed_life {
+            model.xdog.dispatch(&mut encoder);
+            model.life.seed(&mut encoder, &model.storage_texture);
+            gui.reseed_life = false;
+        }
+        model.life.steps(&mut encoder, settings.life_steps);
+        window.queue().submit(Some(encoder.finish()));
+    }
+
+    // The texture shown, as of the last `view` for the compute shaders
+    let processed = match gui.effect {
+        Effect::Life => &model.life.textures()[model.life.front_index()],
+        _ => &model.storage_texture,
+    };
+    let position = model
+        .viewport
+        .cursor()
+        .filter(|_| !pointer_captured)
+        .map(|cursor| model.viewport.window_to_image(cursor, model.storage_texture.size(), [width as f32, height as f32]));
+    let textures = [("Source", &model.texture), ("Processed", processed)];
+    match model.inspector.inspect(window.device(), window.queue(), position, &textures) {
+        Ok(()) if model.inspector.enabled => model.inspector_error = None,
+        Ok(()) => {}
+        Err(err) => {
+            model.inspector.enabled = false;
+            model.inspector_error = Some(err.to_string());
+        }
+    }
+
+    record_frame(app, model);
 }
 
-fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+/// Renders the frame offscreen at the resolution of the recording, if one is running, and writes
+/// it. This blocks until it is read back, the clock only advances once it is written.
+fn record_frame(app: &App, model: &mut Model) {
+    let (Some(render), Some(target)) = (&model.recording.render, &model.recording.target) else {
+        return;
+    };
+    let window = app.main_window();
+    let device = window.device();
+    let target_view = target.view().build();
+
+    let desc = wgpu::CommandEncoderDescriptor {
+        label: Some("recording"),
+    };
+    let mut encoder = device.create_command_encoder(&desc);
+    encode_effect(model, &mut encoder);
+    encode_render(model, render, &target_view, &mut encoder);
+    window.queue().submit(Some(encoder.finish()));
+
+    let image = read_texture(device, window.queue(), target);
+    model.recording.write_frame(image);
+}
+
+/// Rebuilds what uses a shader that changed on disk, when hot reloading is on.
+fn reload_shaders(app: &App, model: &mut Model) {
+    let window = app.main_window();
+    let device = window.device();
+    let settings = &model.gui.settings;
+
+    model.dog.update(device, |sources| {
+        Dog::new(device, &sources[0], &model.texture, &model.storage_texture, settings.uniforms).unwrap()
+    });
+
+    model.render.update(device, |sources| {
+        let texture_views = render_texture_views(&model.storage_texture, &model.life);
+        build_render_pipeline(device, Frame::TEXTURE_FORMAT, window.msaa_samples(), &sources[0], &sources[1], &texture_views)
+    });
+}
+
+fn raw_window_event(app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
     // Let egui handle things like keyboard and mouse input.
     model.gui.egui.handle_raw_event(event);
+    let (width, height) = app.main_window().inner_size_pixels();
+    let captured = egui_captures_pointer(model.gui.egui.ctx());
+    model.viewport.handle_event(event, model.storage_texture.size(), [width as f32, height as f32], captured);
 }
 
 
@@ -280,100 +650,49 @@ fn view(app: &App, model: &Model, frame: Frame) {
 fn compute_pass(app: &App, model: &&Model, frame: &Frame) {
     let window = app.window(frame.window_id()).unwrap();
     let device = window.device();
-    let win_rect = window.rect();
-    let compute = &model.compute;
-
-    // An update for the uniform buffer with the current time.
-    let uniforms = create_uniforms(app.time, model.gui.settings.accentuate);
-    let uniforms_size = std::mem::size_of::<Uniforms>() as wgpu::BufferAddress;
-    let uniforms_bytes = uniforms_as_bytes(&uniforms);
-    let usage = wgpu::BufferUsages::COPY_SRC;
-    let new_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        label: Some("uniform-data-transfer"),
-        contents: uniforms_bytes,
-        usage,
-    });
 
     // The encoder we'll use to encode the compute pass.
     let desc = wgpu::CommandEncoderDescriptor {
         label: Some("convolution-compute"),
     };
     let mut encoder = device.create_command_encoder(&desc);
-    encoder.copy_buffer_to_buffer(
-        &new_uniform_buffer,
-        0,
-        &compute.uniform_buffer,
-        0,
-        uniforms_size,
-    );
-    {
-        let pass_desc = wgpu::ComputePassDescriptor {
-            label: Some("nannou-wgpu_compute_shader-compute_pass"),
-        };
-        let mut cpass = encoder.begin_compute_pass(&pass_desc);
-        cpass.set_pipeline(&compute.pipeline);
-        cpass.set_bind_group(0, &compute.bind_group, &[]);
-        cpass.dispatch_workgroups(1024u32, 1024u32, 1);
-    }
+    encode_effect(model, &mut encoder);
 
     // Submit the compute pass to the device's queue.
     window.queue().submit(Some(encoder.finish()));
 }
 
+/// Runs the compute shaders of the current effect, their result is in the storage texture.
+fn encode_effect(model: &Model, encoder: &mut wgpu::CommandEncoder) {
+    match model.gui.effect {
+        Effect::Dog => model.dog.get().dispatch(encoder),
+        Effect::Xdog => model.xdog.dispatch(encoder),
+        Effect::FlowDog => model.flow_dog.dispatch(encoder),
+        Effect::Life => {}
+    }
+}
+
 fn render_pass(model: &&Model, frame: &Frame) {
-    let shader_model = &model.render;
     //draw.to_frame(app, &frame).unwrap();
     let mut encoder = frame.command_encoder();
+    encode_render(model, model.render.get(), frame.texture_view(), &mut encoder);
+}
+
+/// Draws the result of the current effect onto the target with one of the render pipelines.
+fn encode_render(model: &Model, shader_model: &Render, target: &wgpu::TextureView, encoder: &mut wgpu::CommandEncoder) {
     let mut render_pass = wgpu::RenderPassBuilder::new()
-        .color_attachment(frame.texture_view(), |color| color)
-        .begin(&mut encoder);
-    render_pass.set_bind_group(0, &shader_model.bind_group, &[]);
+        .color_attachment(target, |color| color)
+        .begin(encoder);
+    let bindings = match model.gui.effect {
+        Effect::Life => &shader_model.bindings[1 + model.life.front_index()],
+        _ => &shader_model.bindings[0],
+    };
+    for (i, bind_group) in bindings.groups().iter().enumerate() {
+        render_pass.set_bind_group(i as u32, bind_group, &[]);
+    }
     render_pass.set_pipeline(&shader_model.render_pipeline);
     render_pass.set_vertex_buffer(0, shader_model.vertex_buffer.slice(..));
     let vertex_range = 0..QUAD.len() as u32;
     let instance_range = 0..1;
     render_pass.draw(vertex_range, instance_range);
 }
-
-fn create_uniforms(time: f32, accentuate: f32) -> Uniforms {
-
-    Uniforms {
-        time,
-        accentuate,
-    }
-}
-
-fn create_pipeline_layout(
-    device: &wgpu::Device,
-    bind_group_layout: &wgpu::BindGroupLayout,
-) -> wgpu::PipelineLayout {
-    device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("nannou"),
-        bind_group_layouts: &[&bind_group_layout],
-        push_constant_ranges: &[],
-    })
-}
-
-fn create_compute_pipeline(
-    device: &wgpu::Device,
-    layout: &wgpu::PipelineLayout,
-    cs_mod: &wgpu::ShaderModule,
-) -> wgpu::ComputePipeline {
-    let desc = wgpu::ComputePipelineDescriptor {
-        label: Some("nannou"),
-        layout: Some(layout),
-        module: &cs_mod,
-        entry_point: "main",
-    };
-    device.create_compute_pipeline(&desc)
-}
-
-// See `nannou::wgpu::bytes` docs for why these are necessary.
-
-fn uniforms_as_bytes(uniforms: &Uniforms) -> &[u8] {
-    unsafe { wgpu::bytes::from(uniforms) }
-}
-
-fn vertices_as_bytes(data: &[Vert]) -> &[u8] {
-    unsafe { wgpu::bytes::from_slice(data) }
-}
\ No newline at end of file
@@ -1,24 +1,62 @@
+//! Applies a convolution kernel to an image in a fragment shader.
+//!
+//! Run with `HOT_RELOAD=1` to reload `fs.wgsl` when it is saved.
+//!
+//! The shader renders into a canvas of its own resolution, shown scaled in the window, and can be
+//! exported at any size by rendering it in tiles. Scroll to zoom and drag to pan.
+
 use nannou::image;
-use nannou::image::GenericImageView;
+use nannou::image::{DynamicImage, GenericImageView};
 use nannou::prelude::*;
+use nannou_egui::{Egui, egui};
 
-use lib::shader_processing::model::{ShaderModel};
-use lib::shader_processing::pipeline::{init_shader, wgpu_render_pass};
+use lib::canvas::tiled::{render_tiled_png, DEFAULT_TILE_SIZE};
+use lib::canvas::{Canvas, CanvasPreview};
+use lib::hot_reload;
+use lib::hot_reload::HotReload;
+use lib::preprocessor;
+use lib::preprocessor::Preprocessor;
+use lib::shader_processing::convolution::{ConvolutionKernel, ConvolutionPreset};
+use lib::shader_processing::model::{ConvolutionUniform, ShaderModel};
+use lib::shader_processing::offscreen::OFFSCREEN_TEXTURE_FORMAT;
+use lib::shader_processing::pipeline::{encode_render_pass, init_shader_for_target};
+use lib::viewport::{egui_captures_pointer, Viewport};
 
 fn main() {
-    nannou::app(initialize).run();
+    nannou::app(initialize).update(update).run();
 }
 
+const FS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/shaders/fs.wgsl");
+
 struct Model {
-    shader_model: ShaderModel,
+    shader_model: HotReload<ShaderModel>,
+    image: DynamicImage,
+    canvas: Canvas,
+    preview: CanvasPreview,
+    viewport: Viewport,
+    gui: Gui,
 }
 
-const IDENTITY_CONVOLUTION: [f32; 16] = [
-    0.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.0, 0.0,
-    0.0, 0.0, 0.0, 0.0,
-];
+struct Gui {
+    egui: Egui,
+    settings: Settings,
+    export: Export,
+}
+
+/// Resolutions chosen in the GUI, applied when their button is clicked.
+struct Export {
+    canvas_size: [u32; 2],
+    size: [u32; 2],
+    path: String,
+    status: Option<String>,
+}
+
+struct Settings {
+    preset: ConvolutionPreset,
+    kernel: ConvolutionKernel,
+}
+
+const CONVOLUTION: ConvolutionPreset = ConvolutionPreset::Sharpen;
 
 fn initialize(app: &App) -> Model {
     // Load the image.
@@ -28,20 +66,180 @@ fn initialize(app: &App) -> Model {
 
     let w_id = app
         .new_window()
-        .size(img_w, img_h)
+        .size(1024, 768)
         .view(view)
+        .raw_event(raw_window_event)
         .build()
         .unwrap();
     let window = app.window(w_id).unwrap();
 
-    let fs_desc = wgpu::include_wgsl!("shaders/fs.wgsl");
-    let shader_model = init_shader(&image, &window, fs_desc, IDENTITY_CONVOLUTION);
+    let kernel = CONVOLUTION.kernel();
+    let fs = Preprocessor::new()
+        .with_uniform::<ConvolutionUniform>()
+        .process("fs.wgsl", include_str!("shaders/fs.wgsl"))
+        .unwrap();
+    let shader_model = build_shader_model(&window, &image, fs.descriptor("fs.wgsl"), &kernel);
+
+    // The canvas starts at the resolution of the image, the window only shows it
+    let canvas = Canvas::new(window.device(), [img_w, img_h]);
+    let preview = CanvasPreview::new(window.device(), &canvas, Frame::TEXTURE_FORMAT, window.msaa_samples());
+
+    let gui = Gui {
+        egui: Egui::from_window(&window),
+        settings: Settings {
+            preset: CONVOLUTION,
+            kernel,
+        },
+        export: Export {
+            canvas_size: [img_w, img_h],
+            size: [img_w * 4, img_h * 4],
+            path: "convolution.png".to_string(),
+            status: None,
+        },
+    };
 
     Model {
-        shader_model
+        shader_model: HotReload::watch_if_enabled(shader_model, &[FS_PATH]).with_preprocessor(
+            Preprocessor::new()
+                .with_uniform::<ConvolutionUniform>()
+                .with_search_path(preprocessor::INCLUDE_DIR),
+        ),
+        image,
+        canvas,
+        preview,
+        viewport: Viewport::default(),
+        gui,
     }
 }
 
+/// Renders to the canvas rather than to the window.
+fn build_shader_model(window: &Window, image: &DynamicImage, fs_desc: wgpu::ShaderModuleDescriptor, kernel: &ConvolutionKernel) -> ShaderModel {
+    init_shader_for_target(image, window.device(), window.queue(), OFFSCREEN_TEXTURE_FORMAT, 1, fs_desc, kernel)
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    let window = app.main_window();
+    model.shader_model.update(window.device(), |sources| {
+        let fs_desc = wgpu::ShaderModuleDescriptor {
+            label: Some("fs.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(sources[0].as_str().into()),
+        };
+        build_shader_model(&window, &model.image, fs_desc, &model.gui.settings.kernel)
+    });
+
+    let egui = &mut model.gui.egui;
+    let settings = &mut model.gui.settings;
+    let export = &mut model.gui.export;
+    let viewport = &mut model.viewport;
+    let mut resize_canvas = false;
+    let mut export_png = false;
+
+    egui.set_elapsed_time(update.since_start);
+    let max_texture_size = window.device().limits().max_texture_dimension_2d;
+    let ctx = egui.begin_frame();
+
+    let mut changed = false;
+    egui::Window::new("Convolution").show(&ctx, |ui| {
+        let previous_preset = settings.preset;
+        egui::ComboBox::from_label("Preset")
+            .selected_text(settings.preset.name())
+            .show_ui(ui, |ui| {
+                for preset in ConvolutionPreset::ALL {
+                    ui.selectable_value(&mut settings.preset, preset, preset.name());
+                }
+            });
+        if settings.preset != previous_preset {
+            settings.kernel = settings.preset.kernel();
+            changed = true;
+        }
+
+        // One drag value per weight, laid out like the kernel itself
+        let size = settings.kernel.size();
+        egui::Grid::new("weights").show(ui, |ui| {
+            for row in settings.kernel.weights_mut().chunks_mut(size) {
+                for weight in row {
+                    changed |= ui.add(egui::DragValue::new(weight).speed(0.1)).changed();
+                }
+                ui.end_row();
+            }
+        });
+
+        let mut divisor = settings.kernel.divisor();
+        ui.label("Divisor:");
+        if ui.add(egui::Slider::new(&mut divisor, 0.1..=100.0).logarithmic(true)).changed() {
+            settings.kernel.set_divisor(divisor);
+            changed = true;
+        }
+
+        let mut bias = settings.kernel.bias();
+        ui.label("Bias:");
+        if ui.add(egui::Slider::new(&mut bias, -1.0..=1.0)).changed() {
+            settings.kernel.set_bias(bias);
+            changed = true;
+        }
+
+        ui.separator();
+        ui.collapsing("View", |ui| {
+            viewport.ui(ui);
+        });
+        ui.collapsing("Canvas", |ui| {
+            size_ui(ui, &mut export.canvas_size, max_texture_size);
+            resize_canvas = ui.button("Apply").clicked();
+        });
+        ui.collapsing("Export", |ui| {
+            // Rendered in tiles, so it can be larger than a texture
+            size_ui(ui, &mut export.size, u32::MAX);
+            ui.text_edit_singleline(&mut export.path);
+            export_png = ui.button("Export PNG").clicked();
+            if let Some(status) = &export.status {
+                ui.label(status);
+            }
+        });
+    });
+
+    hot_reload::show_errors(&ctx, model.shader_model.error());
+
+    if changed {
+        model.shader_model.get_mut().set_convolution(window.queue(), &settings.kernel);
+    }
+    if resize_canvas && export.canvas_size != model.canvas.size() {
+        model.canvas = Canvas::new(window.device(), export.canvas_size);
+        model.preview = CanvasPreview::new(window.device(), &model.canvas, Frame::TEXTURE_FORMAT, window.msaa_samples());
+    }
+    let (width, height) = window.inner_size_pixels();
+    model.preview.set_viewport(window.queue(), &model.viewport, [width, height]);
+    if export_png {
+        let shader_model = model.shader_model.get_mut();
+        let result = render_tiled_png(window.device(), window.queue(), shader_model, export.size, DEFAULT_TILE_SIZE, &export.path);
+        export.status = Some(match result {
+            Ok(()) => format!("Saved {}x{} to {}", export.size[0], export.size[1], export.path),
+            Err(err) => err.to_string(),
+        });
+    }
+}
+
+fn size_ui(ui: &mut egui::Ui, size: &mut [u32; 2], max: u32) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut size[0]).clamp_range(1..=max).suffix(" px"));
+        ui.label("x");
+        ui.add(egui::DragValue::new(&mut size[1]).clamp_range(1..=max).suffix(" px"));
+    });
+}
+
+fn raw_window_event(app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+    // Let egui handle things like keyboard and mouse input.
+    model.gui.egui.handle_raw_event(event);
+    let (width, height) = app.main_window().inner_size_pixels();
+    let captured = egui_captures_pointer(model.gui.egui.ctx());
+    model.viewport.handle_event(event, model.canvas.size(), [width as f32, height as f32], captured);
+}
+
 fn view(_app: &App, model: &Model, frame: Frame) {
-    wgpu_render_pass(frame, &model.shader_model);
-}
\ No newline at end of file
+    {
+        // The render passes clear their target, so they have to be recorded before the GUI.
+        let mut encoder = frame.command_encoder();
+        encode_render_pass(&mut encoder, model.canvas.view(), model.shader_model.get());
+        model.preview.encode(&mut encoder, frame.texture_view());
+    }
+    model.gui.egui.draw_to_frame(&frame).unwrap();
+}
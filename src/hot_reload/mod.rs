@@ -0,0 +1,346 @@
+use std::fmt;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use nannou::wgpu;
+use nannou_egui::egui;
+
+use crate::preprocessor::{PreprocessError, Preprocessor, ProcessedShader};
+
+/// Setting this environment variable turns hot reloading on in the examples.
+pub const HOT_RELOAD_VAR: &str = "HOT_RELOAD";
+
+/// Whether `HOT_RELOAD_VAR` is set.
+pub fn enabled() -> bool {
+    std::env::var_os(HOT_RELOAD_VAR).is_some()
+}
+
+/// Why a reloaded shader couldn't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderError {
+    pub path: PathBuf,
+    /// 1-based position of the error, when wgpu reports one.
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+    /// The whole report, including the offending source line.
+    pub details: String,
+}
+
+impl ShaderError {
+    fn from_wgpu(path: &Path, error: &wgpu::Error) -> Self {
+        let details = error.to_string();
+        // The innermost source is the short description, e.g. "expected ';', found '}'"
+        let mut message = details.lines().next().unwrap_or_default().to_string();
+        let mut source: Option<&dyn std::error::Error> = Some(error);
+        while let Some(error) = source {
+            let text = error.to_string();
+            if !text.trim().is_empty() {
+                message = text.trim().to_string();
+            }
+            source = error.source();
+        }
+        let (line, column) = wgsl_location(&details).unzip();
+        ShaderError {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message,
+            details,
+        }
+    }
+
+    fn from_preprocess(error: &PreprocessError) -> Self {
+        let (path, line) = match (error, error.location()) {
+            (_, Some(location)) => (location.file.clone(), Some(location.line)),
+            (PreprocessError::Io { path, .. }, None) => (path.clone(), None),
+            (_, None) => (PathBuf::new(), None),
+        };
+        ShaderError {
+            path,
+            line,
+            column: None,
+            message: error.message(),
+            details: error.to_string(),
+        }
+    }
+
+    /// `build` panicked, e.g. because the bindings of the shader don't match its resources.
+    fn panic(path: &Path, payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|message| message.to_string()))
+            .unwrap_or_else(|| "failed to build the shader".to_string());
+        ShaderError {
+            path: path.to_path_buf(),
+            line: None,
+            column: None,
+            message: message.lines().next().unwrap_or_default().to_string(),
+            details: message,
+        }
+    }
+
+    fn io(path: &Path, error: std::io::Error) -> Self {
+        ShaderError {
+            path: path.to_path_buf(),
+            line: None,
+            column: None,
+            message: error.to_string(),
+            details: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Finds the `wgsl:<line>:<column>` marker that naga adds to its reports.
+fn wgsl_location(report: &str) -> Option<(u32, u32)> {
+    let (_, rest) = report.split_once("wgsl:")?;
+    let mut numbers = rest.split(|c: char| !c.is_ascii_digit());
+    let line = numbers.next()?.parse().ok()?;
+    let column = numbers.next()?.parse().ok()?;
+    Some((line, column))
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// Index of the watched file that this one is, or is included by.
+    root: usize,
+}
+
+impl WatchedFile {
+    fn new(path: &Path, root: usize) -> Self {
+        WatchedFile {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            root,
+        }
+    }
+
+    /// Whether the file was modified since the last call.
+    fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            return modified.is_some();
+        }
+        false
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Something built from WGSL files, like a `ComputeKernel` or a `ShaderModel`, that is rebuilt
+/// when the files change on disk.
+///
+/// Files are polled on every `update`. A change that doesn't compile keeps the last good value
+/// and records the error, so a typo never brings the sketch down.
+///
+/// With a `Preprocessor` the files are preprocessed before `build` sees them, the files they
+/// include are watched too and errors point at the original file and line.
+pub struct HotReload<T> {
+    value: T,
+    files: Vec<WatchedFile>,
+    /// Files on disk included by `files`, found by the last preprocessing.
+    includes: Vec<WatchedFile>,
+    preprocessor: Option<Preprocessor>,
+    /// The watched file that changed last, errors are attributed to it.
+    last_changed: usize,
+    error: Option<ShaderError>,
+}
+
+impl<T> HotReload<T> {
+    /// A value that is never reloaded, for when hot reloading is off.
+    pub fn fixed(value: T) -> Self {
+        HotReload {
+            value,
+            files: Vec::new(),
+            includes: Vec::new(),
+            preprocessor: None,
+            last_changed: 0,
+            error: None,
+        }
+    }
+
+    /// Reloads `value` when any of the files changes, starting from their current state.
+    pub fn watch<P: AsRef<Path>>(value: T, paths: &[P]) -> Self {
+        HotReload {
+            value,
+            files: paths.iter().enumerate().map(|(i, path)| WatchedFile::new(path.as_ref(), i)).collect(),
+            includes: Vec::new(),
+            preprocessor: None,
+            last_changed: 0,
+            error: None,
+        }
+    }
+
+    /// `watch` when `enabled()`, `fixed` otherwise.
+    pub fn watch_if_enabled<P: AsRef<Path>>(value: T, paths: &[P]) -> Self {
+        if enabled() {
+            Self::watch(value, paths)
+        } else {
+            Self::fixed(value)
+        }
+    }
+
+    /// Preprocesses the watched files before building and watches the files they include.
+    pub fn with_preprocessor(mut self, preprocessor: Preprocessor) -> Self {
+        let processed: Vec<_> = self
+            .files
+            .iter()
+            .filter_map(|file| preprocessor.process_file(&file.path).ok())
+            .collect();
+        self.watch_includes(&processed);
+        self.preprocessor = Some(preprocessor);
+        self
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// The error of the last reload, cleared by the next one that succeeds.
+    pub fn error(&self) -> Option<&ShaderError> {
+        self.error.as_ref()
+    }
+
+    /// If any watched file changed, calls `build` with the sources of every watched file (in the
+    /// order they were given) and replaces the value with the result. Returns whether it did.
+    ///
+    /// Validation errors raised by wgpu while building are caught instead of panicking, and so are
+    /// panics of `build` itself.
+    pub fn update<F>(&mut self, device: &wgpu::Device, build: F) -> bool
+    where
+        F: FnOnce(&[String]) -> T,
+    {
+        let mut changed = false;
+        for file in self.files.iter_mut().chain(self.includes.iter_mut()) {
+            if file.changed() {
+                self.last_changed = file.root;
+                changed = true;
+            }
+        }
+        if !changed {
+            return false;
+        }
+
+        let (sources, processed) = match self.read_sources() {
+            Ok(sources) => sources,
+            Err(error) => {
+                self.error = Some(error);
+                return false;
+            }
+        };
+
+        device.push_error_scope(wgpu_upstream::ErrorFilter::Validation);
+        let value = panic::catch_unwind(AssertUnwindSafe(|| build(&sources)));
+        let error = futures::executor::block_on(device.pop_error_scope());
+        match (value, error) {
+            // The wgpu error is usually the cause of the panic and is more precise
+            (_, Some(error)) => {
+                let mut error = ShaderError::from_wgpu(&self.files[self.last_changed].path, &error);
+                // Point at the file and line the preprocessed line comes from
+                let location = processed
+                    .get(self.last_changed)
+                    .zip(error.line)
+                    .and_then(|(shader, line)| shader.location(line));
+                if let Some(location) = location {
+                    error.path = location.file.clone();
+                    error.line = Some(location.line);
+                }
+                self.error = Some(error);
+                false
+            }
+            (Err(payload), None) => {
+                self.error = Some(ShaderError::panic(&self.files[self.last_changed].path, payload));
+                false
+            }
+            (Ok(value), None) => {
+                self.value = value;
+                self.error = None;
+                true
+            }
+        }
+    }
+
+    /// The sources to build from and, with a preprocessor, the shaders they were processed into.
+    fn read_sources(&mut self) -> Result<(Vec<String>, Vec<ProcessedShader>), ShaderError> {
+        let Some(preprocessor) = &self.preprocessor else {
+            let mut sources = Vec::with_capacity(self.files.len());
+            for file in &self.files {
+                let source = fs::read_to_string(&file.path).map_err(|err| ShaderError::io(&file.path, err))?;
+                sources.push(source);
+            }
+            return Ok((sources, Vec::new()));
+        };
+
+        let mut processed = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            let shader = preprocessor.process_file(&file.path).map_err(|err| ShaderError::from_preprocess(&err))?;
+            processed.push(shader);
+        }
+        self.watch_includes(&processed);
+        let sources = processed.iter().map(|shader| shader.source.clone()).collect();
+        Ok((sources, processed))
+    }
+
+    fn watch_includes(&mut self, processed: &[ProcessedShader]) {
+        self.includes.clear();
+        for (root, shader) in processed.iter().enumerate() {
+            for path in shader.files() {
+                let watched = self.files.iter().chain(self.includes.iter()).any(|file| &file.path == path);
+                if !watched {
+                    self.includes.push(WatchedFile::new(path, root));
+                }
+            }
+        }
+    }
+}
+
+/// Shows the given errors in a window on top of the sketch, does nothing if there are none.
+pub fn show_errors<'a, I>(ctx: &egui::Context, errors: I)
+where
+    I: IntoIterator<Item = &'a ShaderError>,
+{
+    let errors: Vec<_> = errors.into_iter().collect();
+    if errors.is_empty() {
+        return;
+    }
+    egui::Window::new("Shader errors")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -10.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            for error in errors {
+                ui.colored_label(egui::Color32::LIGHT_RED, error.to_string());
+                egui::CollapsingHeader::new("Details")
+                    .id_source(&error.path)
+                    .show(ui, |ui| {
+                        ui.label(egui::RichText::new(&error.details).monospace());
+                    });
+            }
+        });
+}
@@ -0,0 +1,212 @@
+use nannou::image::{Rgba, RgbaImage};
+use nannou::wgpu;
+use nannou_egui::egui;
+
+use crate::shader_processing::offscreen::{start_texture_read, OffscreenError, PendingRead};
+
+/// Side of a pixel in the loupe, in points.
+const LOUPE_CELL: f32 = 10.0;
+/// Distance of the overlay from the cursor, in points, so that it doesn't hide the pixel.
+const CURSOR_OFFSET: f32 = 20.0;
+
+/// Shows the values of the pixel under the cursor in a few textures, e.g. the source and the
+/// processed image, with a loupe of the pixels around it.
+///
+/// The pixels are read back from the GPU by `inspect` while enabled, and shown next to the cursor
+/// by `show`. Reads don't wait for the GPU, the last sample is shown until the next one arrives.
+pub struct Inspector {
+    pub enabled: bool,
+    /// Pixels shown in the loupe on each side of the one under the cursor.
+    pub radius: u32,
+    sample: Option<Sample>,
+    /// The read started after `sample`, with the copy of each layer until it is done.
+    pending: Option<(Sample, Vec<Option<PendingRead>>)>,
+}
+
+/// The pixels around the cursor, in every texture inspected.
+pub struct Sample {
+    /// The pixel under the cursor, in image coordinates.
+    pub position: [u32; 2],
+    /// Image coordinates of the top left pixel of the neighbourhoods.
+    pub origin: [u32; 2],
+    /// The `radius` of the inspector at the time.
+    pub radius: u32,
+    pub layers: Vec<Layer>,
+}
+
+/// The neighbourhood of the cursor in one texture.
+pub struct Layer {
+    pub name: String,
+    /// Cut off at the edges of the texture.
+    pub pixels: RgbaImage,
+    /// Whether the texture has an sRGB format, its bytes are then encoded and shaders read them
+    /// decoded.
+    pub srgb: bool,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Inspector {
+            enabled: false,
+            radius: 6,
+            sample: None,
+            pending: None,
+        }
+    }
+}
+
+impl Inspector {
+    /// The pixels of the last read that finished, if the cursor was over the image.
+    pub fn sample(&self) -> Option<&Sample> {
+        self.sample.as_ref()
+    }
+
+    /// Collects the read started by the previous call once the GPU is done with it, then starts
+    /// reading the pixels around `position` in each of `textures`. Never blocks: `sample` stays
+    /// the previous one until then. Nothing is read while disabled or when `position` is outside
+    /// of the image.
+    ///
+    /// `position` is in image coordinates, e.g. from `Viewport::window_to_image`, and the textures
+    /// are of the same size. They need 4 bytes per pixel and `wgpu::TextureUsages::COPY_SRC`.
+    pub fn inspect(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        position: Option<[f32; 2]>,
+        textures: &[(&str, &wgpu::Texture)],
+    ) -> Result<(), OffscreenError> {
+        let (true, Some(position), Some((_, texture))) = (self.enabled, position, textures.first()) else {
+            self.sample = None;
+            self.pending = None;
+            return Ok(());
+        };
+        let [width, height] = texture.size();
+        if position[0] < 0.0 || position[1] < 0.0 || position[0] >= width as f32 || position[1] >= height as f32 {
+            self.sample = None;
+            self.pending = None;
+            return Ok(());
+        }
+
+        if let Some((sample, reads)) = &mut self.pending {
+            device.poll(wgpu::Maintain::Poll);
+            for (layer, read) in sample.layers.iter_mut().zip(reads.iter_mut()) {
+                if let Some(pixels) = read.as_ref().and_then(PendingRead::try_finish) {
+                    layer.pixels = pixels?;
+                    *read = None;
+                }
+            }
+            if reads.iter().any(Option::is_some) {
+                return Ok(());
+            }
+            self.sample = self.pending.take().map(|(sample, _)| sample);
+        }
+
+        let position = position.map(|coordinate| coordinate as u32);
+        let origin = position.map(|coordinate| coordinate.saturating_sub(self.radius));
+        let size = [
+            (position[0] + self.radius + 1).min(width) - origin[0],
+            (position[1] + self.radius + 1).min(height) - origin[1],
+        ];
+        let reads = textures
+            .iter()
+            .map(|(_, texture)| Some(start_texture_read(device, queue, texture, origin, size)))
+            .collect();
+        let layers = textures
+            .iter()
+            .map(|(name, texture)| Layer {
+                name: name.to_string(),
+                pixels: RgbaImage::new(0, 0),
+                srgb: texture.format().is_srgb(),
+            })
+            .collect();
+        let sample = Sample {
+            position,
+            origin,
+            radius: self.radius,
+            layers,
+        };
+        self.pending = Some((sample, reads));
+        Ok(())
+    }
+
+    /// Shows a widget per setting. Returns whether any of them changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.checkbox(&mut self.enabled, "Inspect pixels").changed();
+        ui.label("Loupe radius:");
+        changed |= ui.add(egui::Slider::new(&mut self.radius, 1..=16).suffix(" px")).changed();
+        changed
+    }
+
+    /// Shows the last sample next to `cursor`, in window pixels like `Viewport::cursor`: the image
+    /// coordinates, then the values and a loupe of each texture.
+    pub fn show(&self, ctx: &egui::Context, cursor: Option<[f32; 2]>) {
+        let (Some(sample), Some(cursor)) = (&self.sample, cursor) else {
+            return;
+        };
+        let pixels_per_point = ctx.pixels_per_point();
+        let position = egui::pos2(cursor[0] / pixels_per_point, cursor[1] / pixels_per_point) + egui::Vec2::splat(CURSOR_OFFSET);
+        // Not interactable, so that the viewport can still be dragged under it
+        egui::Area::new("pixel-inspector")
+            .fixed_pos(position)
+            .order(egui::Order::Tooltip)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("x: {}, y: {}", sample.position[0], sample.position[1]));
+                    ui.horizontal(|ui| {
+                        for layer in &sample.layers {
+                            ui.vertical(|ui| layer_ui(ui, sample, layer));
+                        }
+                    });
+                });
+            });
+    }
+}
+
+impl Sample {
+    /// The pixel under the cursor in a layer.
+    pub fn pixel(&self, layer: &Layer) -> Rgba<u8> {
+        *layer.pixels.get_pixel(self.position[0] - self.origin[0], self.position[1] - self.origin[1])
+    }
+}
+
+impl Layer {
+    /// A pixel as shaders read it, from 0 to 1 with the colors decoded for sRGB formats.
+    pub fn to_float(&self, pixel: Rgba<u8>) -> [f32; 4] {
+        let [r, g, b, a] = pixel.0;
+        let color = |value: u8| {
+            if self.srgb {
+                egui::ecolor::linear_f32_from_gamma_u8(value)
+            } else {
+                value as f32 / 255.0
+            }
+        };
+        [color(r), color(g), color(b), a as f32 / 255.0]
+    }
+}
+
+fn layer_ui(ui: &mut egui::Ui, sample: &Sample, layer: &Layer) {
+    let radius = sample.radius;
+    let pixel = sample.pixel(layer);
+    let [r, g, b, a] = layer.to_float(pixel);
+    ui.strong(&layer.name);
+    ui.monospace(format!("{:.3} {:.3} {:.3} {:.3}", r, g, b, a));
+    ui.monospace(format!("{:>5} {:>5} {:>5} {:>5}", pixel[0], pixel[1], pixel[2], pixel[3]));
+
+    // One flat cell per pixel, the neighbourhood of a pixel by the edge is left empty past it
+    let side = (2 * radius + 1) as f32 * LOUPE_CELL;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(side, side), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let cell = egui::Vec2::splat(LOUPE_CELL);
+    for (x, y, pixel) in layer.pixels.enumerate_pixels() {
+        let column = sample.origin[0] + x + radius - sample.position[0];
+        let row = sample.origin[1] + y + radius - sample.position[1];
+        let [r, g, b, _] = layer.to_float(*pixel);
+        let min = rect.min + egui::vec2(column as f32, row as f32) * LOUPE_CELL;
+        // Opaque, the alpha is in the values above
+        painter.rect_filled(egui::Rect::from_min_size(min, cell), 0.0, egui::Rgba::from_rgb(r, g, b));
+    }
+    let center = egui::Rect::from_min_size(rect.min + egui::Vec2::splat(radius as f32 * LOUPE_CELL), cell);
+    painter.rect_stroke(center, 0.0, egui::Stroke::new(1.0, egui::Color32::RED));
+}
@@ -0,0 +1,267 @@
+use std::f32::consts::TAU;
+
+pub use shader_uniform_derive::Interpolate;
+
+/// A value that can be blended with another one of the same type, e.g. to morph between presets.
+///
+/// Usually derived, fields are blended with their own `Interpolate` unless annotated:
+///
+/// ```ignore
+/// #[derive(Clone, Interpolate)]
+/// struct Settings {
+///     // Linear
+///     scale: f32,
+///     // Perceptual, in Oklab
+///     #[interpolate(color)]
+///     color: [f32; 3],
+///     // Along the shortest arc, also `radians`
+///     #[interpolate(degrees)]
+///     rotation: f32,
+///     // Switches halfway
+///     #[interpolate(step)]
+///     invert: u32,
+///     // Keeps the value of `self`
+///     #[interpolate(skip)]
+///     time: f32,
+/// }
+/// ```
+pub trait Interpolate {
+    /// `self` at `t = 0` and `other` at `t = 1`.
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        lerp(*self, *other, t)
+    }
+}
+
+impl Interpolate for i32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        lerp(*self as f32, *other as f32, t).round() as i32
+    }
+}
+
+impl Interpolate for u32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        lerp(*self as f32, *other as f32, t).round() as u32
+    }
+}
+
+impl Interpolate for bool {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        step(self, other, t)
+    }
+}
+
+/// Component-wise, for vectors.
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        std::array::from_fn(|i| self[i].interpolate(&other[i], t))
+    }
+}
+
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// `a` for the first half, `b` for the second one, for values that can't be blended.
+pub fn step<T: Clone>(a: &T, b: &T, t: f32) -> T {
+    if t < 0.5 {
+        a.clone()
+    } else {
+        b.clone()
+    }
+}
+
+/// Turns along the shortest arc, the result is in `[0, TAU)`.
+pub fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    lerp_turn(a, b, t, TAU)
+}
+
+/// `lerp_angle` in degrees, the result is in `[0, 360)`.
+pub fn lerp_angle_degrees(a: f32, b: f32, t: f32) -> f32 {
+    lerp_turn(a, b, t, 360.0)
+}
+
+fn lerp_turn(a: f32, b: f32, t: f32, turn: f32) -> f32 {
+    let mut delta = (b - a).rem_euclid(turn);
+    if delta > turn / 2.0 {
+        delta -= turn;
+    }
+    // `rem_euclid` rounds tiny negative values up to `turn`
+    let angle = (a + delta * t).rem_euclid(turn);
+    if angle >= turn {
+        0.0
+    } else {
+        angle
+    }
+}
+
+/// Blends two linear RGB colors in Oklab, so the lightness and hue change evenly instead of going
+/// through the greys of a plain RGB blend.
+pub fn lerp_color(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    let lab = linear_srgb_to_oklab(a).interpolate(&linear_srgb_to_oklab(b), t);
+    oklab_to_linear_srgb(lab)
+}
+
+/// `lerp_color` with a linearly blended straight alpha.
+pub fn lerp_color_alpha(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let [red, green, blue] = lerp_color([a[0], a[1], a[2]], [b[0], b[1], b[2]], t);
+    [red, green, blue, lerp(a[3], b[3], t)]
+}
+
+// Matrices from https://bottosson.github.io/posts/oklab/
+fn linear_srgb_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = 0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b;
+    let m = 0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b;
+    let s = 0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b;
+    let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+fn oklab_to_linear_srgb([lightness, a, b]: [f32; 3]) -> [f32; 3] {
+    let l = lightness + 0.396_337_78 * a + 0.215_803_76 * b;
+    let m = lightness - 0.105_561_346 * a - 0.063_854_17 * b;
+    let s = lightness - 0.089_484_18 * a - 1.291_485_5 * b;
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+/// Blends a list of values, `position` goes from `0` for the first one to `len - 1` for the last,
+/// through each of them in order. `None` if the list is empty.
+pub fn blend<T: Interpolate + Clone>(values: &[T], position: f32) -> Option<T> {
+    let last = values.len().checked_sub(1)?;
+    let position = position.clamp(0.0, last as f32);
+    let index = (position.floor() as usize).min(last.saturating_sub(1));
+    match values.get(index + 1) {
+        Some(next) => Some(values[index].interpolate(next, position - index as f32)),
+        None => Some(values[index].clone()),
+    }
+}
+
+/// Glides from one value to another over time, e.g. to go to a preset without snapping to it.
+#[derive(Debug, Clone)]
+pub struct Transition<T> {
+    from: T,
+    to: T,
+    start: f32,
+    duration: f32,
+}
+
+impl<T: Interpolate + Clone> Transition<T> {
+    /// Times are in seconds, usually `app.time`.
+    pub fn new(from: T, to: T, start: f32, duration: f32) -> Self {
+        Transition {
+            from,
+            to,
+            start,
+            duration,
+        }
+    }
+
+    /// From `0` at the start to `1` once finished.
+    pub fn progress(&self, time: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        ((time - self.start) / self.duration).clamp(0.0, 1.0)
+    }
+
+    pub fn is_finished(&self, time: f32) -> bool {
+        self.progress(time) >= 1.0
+    }
+
+    /// The value at the given time, eased in and out.
+    pub fn value(&self, time: f32) -> T {
+        let t = self.progress(time);
+        self.from.interpolate(&self.to, t * t * (3.0 - 2.0 * t))
+    }
+
+    pub fn target(&self) -> &T {
+        &self.to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn turns_along_the_shortest_arc() {
+        assert_close(lerp_angle_degrees(350.0, 10.0, 0.5), 0.0);
+        assert_close(lerp_angle_degrees(350.0, 10.0, 0.25), 355.0);
+        assert_close(lerp_angle_degrees(350.0, 10.0, 0.75), 5.0);
+        assert_close(lerp_angle_degrees(10.0, 350.0, 0.75), 355.0);
+        assert_close(lerp_angle_degrees(90.0, 180.0, 0.5), 135.0);
+        assert_close(lerp_angle(0.1, TAU - 0.1, 0.5), 0.0);
+    }
+
+    #[test]
+    fn keeps_angles_below_a_full_turn() {
+        // -1e-8 is rounded up to a full turn by `rem_euclid`
+        assert_eq!((-1e-8f32).rem_euclid(360.0), 360.0);
+        assert_eq!(lerp_angle_degrees(0.0, 359.0, 1e-8), 0.0);
+        for i in 0..=100 {
+            let angle = lerp_angle_degrees(0.0, 359.0, i as f32 / 100.0);
+            assert!((0.0..360.0).contains(&angle), "{}", angle);
+        }
+    }
+
+    #[test]
+    fn blends_colors_from_one_end_to_the_other() {
+        let (red, blue) = ([1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+        for (actual, expected) in [(lerp_color(red, blue, 0.0), red), (lerp_color(red, blue, 1.0), blue)] {
+            for channel in 0..3 {
+                assert_close(actual[channel], expected[channel]);
+            }
+        }
+        let alpha = lerp_color_alpha([0.2, 0.4, 0.6, 0.0], [0.2, 0.4, 0.6, 1.0], 0.25);
+        assert_close(alpha[1], 0.4);
+        assert_close(alpha[3], 0.25);
+    }
+
+    #[test]
+    fn blends_lists_in_order() {
+        assert_eq!(blend::<f32>(&[], 0.5), None);
+        assert_eq!(blend(&[4.0], 0.0), Some(4.0));
+        assert_eq!(blend(&[4.0], 3.0), Some(4.0));
+
+        let values = [0.0, 10.0, 30.0];
+        assert_eq!(blend(&values, 0.5), Some(5.0));
+        assert_eq!(blend(&values, 1.0), Some(10.0));
+        assert_eq!(blend(&values, 1.5), Some(20.0));
+        assert_eq!(blend(&values, 2.0), Some(30.0));
+        assert_eq!(blend(&values, -1.0), Some(0.0));
+        assert_eq!(blend(&values, 7.0), Some(30.0));
+    }
+
+    #[test]
+    fn finishes_transitions_without_duration_at_once() {
+        for duration in [0.0, -1.0] {
+            let transition = Transition::new(0.0, 1.0, 5.0, duration);
+            assert_eq!(transition.progress(0.0), 1.0);
+            assert!(transition.is_finished(5.0));
+            assert_eq!(transition.value(5.0), 1.0);
+        }
+
+        let transition = Transition::new(0.0, 1.0, 5.0, 2.0);
+        assert_eq!(transition.progress(4.0), 0.0);
+        assert_eq!(transition.progress(6.0), 0.5);
+        assert_eq!(transition.value(6.0), 0.5);
+        assert!(!transition.is_finished(6.9));
+        assert!(transition.is_finished(7.0));
+    }
+}
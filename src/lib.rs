@@ -1,4 +1,18 @@
-// TODO: add reusable compute shader logic here
+// Lets the code generated by the derives of `shader_uniform_derive` use `::lib` here too
+extern crate self as lib;
 
 pub mod shader_processing;
-pub mod compute_kernel;
\ No newline at end of file
+pub mod canvas;
+pub mod compute_kernel;
+pub mod controls;
+pub mod hot_reload;
+pub mod inspector;
+pub mod interpolation;
+pub mod preprocessor;
+pub mod presets;
+pub mod random;
+pub mod recording;
+pub mod reflection;
+pub mod render_graph;
+pub mod uniforms;
+pub mod viewport;
\ No newline at end of file
@@ -0,0 +1,564 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use nannou::wgpu;
+
+use crate::uniforms::WgslUniform;
+
+/// Where the built-in includes live on disk, add it with `with_search_path` to hot reload them
+/// too.
+pub const INCLUDE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/preprocessor/shaders");
+
+/// Includes embedded in the library, available to every shader.
+pub const BUILTIN_INCLUDES: &[(&str, &str)] = &[
+    ("common/color.wgsl", include_str!("shaders/common/color.wgsl")),
+    ("common/random.wgsl", include_str!("shaders/common/random.wgsl")),
+];
+
+/// A line of a source file, 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: u32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessError {
+    /// The included file is neither next to the including one, in a search path nor embedded.
+    IncludeNotFound { location: SourceLocation, path: String },
+    Io { path: PathBuf, message: String },
+    /// A directive is missing its argument, e.g. `#include` without a quoted path.
+    InvalidDirective { location: SourceLocation, message: String },
+    UnknownDirective { location: SourceLocation, directive: String },
+    /// `#else` or `#endif` without an `#ifdef` or `#ifndef`.
+    UnmatchedDirective { location: SourceLocation, directive: String },
+    /// An `#ifdef` or `#ifndef` without `#endif`.
+    UnterminatedConditional { location: SourceLocation },
+}
+
+impl PreprocessError {
+    /// The line the error was found at, if any.
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            PreprocessError::IncludeNotFound { location, .. }
+            | PreprocessError::InvalidDirective { location, .. }
+            | PreprocessError::UnknownDirective { location, .. }
+            | PreprocessError::UnmatchedDirective { location, .. }
+            | PreprocessError::UnterminatedConditional { location } => Some(location),
+            PreprocessError::Io { .. } => None,
+        }
+    }
+
+    /// The error without its location.
+    pub fn message(&self) -> String {
+        match self {
+            PreprocessError::IncludeNotFound { path, .. } => format!("can't find include \"{}\"", path),
+            PreprocessError::Io { message, .. } => message.clone(),
+            PreprocessError::InvalidDirective { message, .. } => message.clone(),
+            PreprocessError::UnknownDirective { directive, .. } => format!("unknown directive `#{}`", directive),
+            PreprocessError::UnmatchedDirective { directive, .. } => {
+                format!("`#{}` without a matching `#ifdef` or `#ifndef`", directive)
+            }
+            PreprocessError::UnterminatedConditional { .. } => "missing `#endif`".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreprocessError::Io { path, .. } => write!(f, "{}: {}", path.display(), self.message()),
+            _ => write!(f, "{}: {}", self.location().expect("only io errors have no location"), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// The result of preprocessing, with a map from its lines back to the original files.
+#[derive(Debug, Clone)]
+pub struct ProcessedShader {
+    pub source: String,
+    /// Origin of every line of `source`.
+    lines: Vec<SourceLocation>,
+    /// Files read from disk, the main one included.
+    files: Vec<PathBuf>,
+}
+
+impl ProcessedShader {
+    /// Where a 1-based line of `source` comes from, e.g. to translate the lines of wgpu errors.
+    pub fn location(&self, line: u32) -> Option<&SourceLocation> {
+        self.lines.get((line as usize).checked_sub(1)?)
+    }
+
+    /// Every file read from disk while preprocessing, to watch them for changes.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn descriptor<'a>(&'a self, label: &'a str) -> wgpu::ShaderModuleDescriptor<'a> {
+        wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&self.source)),
+        }
+    }
+}
+
+/// Resolves `#include "path"`, `#define NAME value`, `#undef NAME` and
+/// `#ifdef NAME` / `#ifndef NAME` / `#else` / `#endif` in WGSL.
+///
+/// Includes are looked up next to the including file, then in the search paths and then in the
+/// embedded sources (`BUILTIN_INCLUDES` and the ones given to `with_embedded`). A file is only
+/// included once, later `#include`s of it are ignored.
+///
+/// Defines with a value replace every occurrence of their name as a whole word.
+///
+/// ```ignore
+/// let shader = Preprocessor::new()
+///     .with_uniform::<XdogUniforms>()
+///     .with_define("COLOR_OUTPUT", "")
+///     .process("xdog.wgsl", include_str!("shaders/xdog.wgsl"))?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    defines: HashMap<String, String>,
+    embedded: HashMap<String, Cow<'static, str>>,
+    search_paths: Vec<PathBuf>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Preprocessor::new()
+    }
+}
+
+impl Preprocessor {
+    /// A preprocessor that only knows about `BUILTIN_INCLUDES`, uniform structs are added with
+    /// `with_uniform` by the code that binds them.
+    pub fn new() -> Self {
+        let embedded = BUILTIN_INCLUDES
+            .iter()
+            .map(|(path, source)| (path.to_string(), Cow::Borrowed(*source)))
+            .collect();
+        Preprocessor {
+            defines: HashMap::new(),
+            embedded,
+            search_paths: Vec::new(),
+        }
+    }
+
+    /// Defines `name` before the first line, an empty value only makes it visible to `#ifdef`.
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Makes a source, usually from `include_str!`, available to `#include` under `path`.
+    pub fn with_embedded(mut self, path: &str, source: impl Into<Cow<'static, str>>) -> Self {
+        self.embedded.insert(normalize(Path::new(path)), source.into());
+        self
+    }
+
+    /// Makes the WGSL declaration of `T` available as `#include "uniforms/<name>.wgsl"`, where
+    /// `<name>` is `T::wgsl_name()`.
+    pub fn with_uniform<T: WgslUniform>(self) -> Self {
+        let path = format!("uniforms/{}.wgsl", T::wgsl_name());
+        self.with_embedded(&path, T::wgsl_struct())
+    }
+
+    pub fn with_search_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.search_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Preprocesses a source that is already in memory, `name` is used for errors and to resolve
+    /// includes relative to it among the embedded sources.
+    pub fn process(&self, name: &str, source: &str) -> Result<ProcessedShader, PreprocessError> {
+        let mut state = State::new(&self.defines);
+        let name = PathBuf::from(normalize(Path::new(name)));
+        state.included.insert(name.clone());
+        self.process_source(&mut state, &name, Origin::Embedded, source)?;
+        Ok(state.finish())
+    }
+
+    /// Reads and preprocesses a file, includes are also looked up next to it on disk.
+    pub fn process_file<P: AsRef<Path>>(&self, path: P) -> Result<ProcessedShader, PreprocessError> {
+        let path = path.as_ref();
+        let source = read(path)?;
+        let mut state = State::new(&self.defines);
+        state.included.insert(path.to_path_buf());
+        state.files.push(path.to_path_buf());
+        self.process_source(&mut state, path, Origin::Disk, &source)?;
+        Ok(state.finish())
+    }
+
+    fn process_source(&self, state: &mut State, file: &Path, origin: Origin, source: &str) -> Result<(), PreprocessError> {
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let location = SourceLocation {
+                file: file.to_path_buf(),
+                line: i as u32 + 1,
+            };
+            let active = conditionals.last().is_none_or(|conditional| conditional.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    state.push_line(line, location);
+                }
+                continue;
+            };
+            let (name, argument) = match directive.trim().split_once(char::is_whitespace) {
+                Some((name, argument)) => (name, argument.trim()),
+                None => (directive.trim(), ""),
+            };
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = state.defines.contains_key(word(argument, "a name", &location)?);
+                    conditionals.push(Conditional {
+                        active: active && defined == (name == "ifdef"),
+                        parent_active: active,
+                        location,
+                    });
+                }
+                "else" => {
+                    let conditional = conditionals.last_mut().ok_or_else(|| unmatched(name, &location))?;
+                    conditional.active = conditional.parent_active && !conditional.active;
+                }
+                "endif" => {
+                    conditionals.pop().ok_or_else(|| unmatched(name, &location))?;
+                }
+                _ if !active => {}
+                "include" => {
+                    let path = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| PreprocessError::InvalidDirective {
+                            location: location.clone(),
+                            message: "`#include` expects a path in double quotes".to_string(),
+                        })?;
+                    let (included, origin, source) = self.resolve(file, origin, path).ok_or_else(|| {
+                        PreprocessError::IncludeNotFound {
+                            location: location.clone(),
+                            path: path.to_string(),
+                        }
+                    })?;
+                    if state.included.insert(included.clone()) {
+                        if origin == Origin::Disk {
+                            state.files.push(included.clone());
+                        }
+                        let source = match source {
+                            Some(source) => source,
+                            None => Cow::Owned(read(&included)?),
+                        };
+                        self.process_source(state, &included, origin, &source)?;
+                    }
+                }
+                "define" => {
+                    let (define, value) = match argument.split_once(char::is_whitespace) {
+                        Some((define, value)) => (define, value.trim()),
+                        None => (argument, ""),
+                    };
+                    let define = word(define, "a name", &location)?;
+                    state.defines.insert(define.to_string(), value.to_string());
+                }
+                "undef" => {
+                    let define = word(argument, "a name", &location)?;
+                    state.defines.remove(define);
+                }
+                _ => {
+                    return Err(PreprocessError::UnknownDirective {
+                        location,
+                        directive: name.to_string(),
+                    })
+                }
+            }
+        }
+
+        match conditionals.pop() {
+            Some(conditional) => Err(PreprocessError::UnterminatedConditional {
+                location: conditional.location,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Finds an include, returns its path, where it comes from and its source if it's embedded.
+    fn resolve(&self, from: &Path, origin: Origin, include: &str) -> Option<(PathBuf, Origin, Option<Cow<'static, str>>)> {
+        let relative = from.parent().unwrap_or(Path::new("")).join(include);
+        match origin {
+            Origin::Disk if relative.is_file() => return Some((relative, Origin::Disk, None)),
+            Origin::Embedded => {
+                if let Some(source) = self.embedded.get(&normalize(&relative)) {
+                    return Some((PathBuf::from(normalize(&relative)), Origin::Embedded, Some(source.clone())));
+                }
+            }
+            _ => {}
+        }
+        for search_path in &self.search_paths {
+            let path = search_path.join(include);
+            if path.is_file() {
+                return Some((path, Origin::Disk, None));
+            }
+        }
+        let name = normalize(Path::new(include));
+        let source = self.embedded.get(&name)?;
+        Some((PathBuf::from(name), Origin::Embedded, Some(source.clone())))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Origin {
+    Disk,
+    Embedded,
+}
+
+struct Conditional {
+    active: bool,
+    /// Whether the enclosing block is active, `#else` can only activate lines if it is.
+    parent_active: bool,
+    location: SourceLocation,
+}
+
+struct State {
+    defines: HashMap<String, String>,
+    source: String,
+    lines: Vec<SourceLocation>,
+    files: Vec<PathBuf>,
+    included: HashSet<PathBuf>,
+}
+
+impl State {
+    fn new(defines: &HashMap<String, String>) -> Self {
+        State {
+            defines: defines.clone(),
+            source: String::new(),
+            lines: Vec::new(),
+            files: Vec::new(),
+            included: HashSet::new(),
+        }
+    }
+
+    fn push_line(&mut self, line: &str, location: SourceLocation) {
+        self.source.push_str(&substitute(line, &self.defines));
+        self.source.push('\n');
+        self.lines.push(location);
+    }
+
+    fn finish(self) -> ProcessedShader {
+        ProcessedShader {
+            source: self.source,
+            lines: self.lines,
+            files: self.files,
+        }
+    }
+}
+
+/// Replaces the identifiers that have a non empty define with its value.
+fn substitute<'a>(line: &'a str, defines: &HashMap<String, String>) -> Cow<'a, str> {
+    if defines.values().all(|value| value.is_empty()) {
+        return Cow::Borrowed(line);
+    }
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(is_identifier_char) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c: char| !is_identifier_char(c)).unwrap_or(rest.len());
+        let identifier = &rest[..end];
+        match defines.get(identifier) {
+            Some(value) if !value.is_empty() => result.push_str(value),
+            _ => result.push_str(identifier),
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn word<'a>(argument: &'a str, expected: &str, location: &SourceLocation) -> Result<&'a str, PreprocessError> {
+    if !argument.is_empty() && argument.chars().all(is_identifier_char) {
+        Ok(argument)
+    } else {
+        Err(PreprocessError::InvalidDirective {
+            location: location.clone(),
+            message: format!("expected {}, found `{}`", expected, argument),
+        })
+    }
+}
+
+fn unmatched(directive: &str, location: &SourceLocation) -> PreprocessError {
+    PreprocessError::UnmatchedDirective {
+        location: location.clone(),
+        directive: directive.to_string(),
+    }
+}
+
+fn read(path: &Path) -> Result<String, PreprocessError> {
+    fs::read_to_string(path).map_err(|err| PreprocessError::Io {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    })
+}
+
+/// `a/./b/../c.wgsl` as `a/c.wgsl`, with forward slashes, to look up embedded sources.
+fn normalize(path: &Path) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                components.pop();
+            }
+            Component::Normal(name) => components.push(name.to_str().unwrap_or_default()),
+            _ => {}
+        }
+    }
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(file: &str, line: u32) -> SourceLocation {
+        SourceLocation {
+            file: PathBuf::from(file),
+            line,
+        }
+    }
+
+    #[test]
+    fn includes_nested_files() {
+        let preprocessor = Preprocessor::new()
+            .with_embedded("lib/a.wgsl", "#include \"b.wgsl\"\nlet a = 1;")
+            .with_embedded("lib/b.wgsl", "let b = 2;");
+        let shader = preprocessor.process("main.wgsl", "#include \"lib/a.wgsl\"\nlet main = 0;").unwrap();
+        assert_eq!(shader.source, "let b = 2;\nlet a = 1;\nlet main = 0;\n");
+    }
+
+    #[test]
+    fn includes_every_file_once() {
+        let preprocessor = Preprocessor::new()
+            .with_embedded("a.wgsl", "#include \"b.wgsl\"\nlet a = 1;")
+            .with_embedded("b.wgsl", "#include \"a.wgsl\"\nlet b = 2;");
+        let shader = preprocessor.process("main.wgsl", "#include \"a.wgsl\"\n#include \"b.wgsl\"").unwrap();
+        assert_eq!(shader.source, "let b = 2;\nlet a = 1;\n");
+
+        let shader = preprocessor.process("a.wgsl", "#include \"b.wgsl\"\nlet a = 1;").unwrap();
+        assert_eq!(shader.source, "let b = 2;\nlet a = 1;\n");
+    }
+
+    #[test]
+    fn reports_missing_includes() {
+        let preprocessor = Preprocessor::new().with_embedded("a.wgsl", "let a = 1;\n#include \"missing.wgsl\"");
+        let error = preprocessor.process("main.wgsl", "#include \"a.wgsl\"").unwrap_err();
+        assert_eq!(
+            error,
+            PreprocessError::IncludeNotFound {
+                location: location("a.wgsl", 2),
+                path: "missing.wgsl".to_string()
+            }
+        );
+        assert_eq!(error.to_string(), "a.wgsl:2: can't find include \"missing.wgsl\"");
+    }
+
+    #[test]
+    fn substitutes_defines_as_whole_words() {
+        let preprocessor = Preprocessor::new().with_define("SIZE", "8u");
+        let shader = preprocessor.process("main.wgsl", "#define COUNT 3\nlet x = SIZE * COUNT + SIZE_2;").unwrap();
+        assert_eq!(shader.source, "let x = 8u * 3 + SIZE_2;\n");
+    }
+
+    #[test]
+    fn keeps_the_active_branches() {
+        let source = "\
+#ifdef COLOR
+let color = 1;
+#ifndef GRAY
+let gray = 0;
+#else
+let gray = 1;
+#endif
+#else
+let color = 0;
+#ifdef GRAY
+let gray = 1;
+#endif
+#endif
+#undef COLOR
+#ifdef COLOR
+let undefined = 1;
+#endif";
+        let preprocessor = Preprocessor::new().with_define("COLOR", "");
+        assert_eq!(preprocessor.process("main.wgsl", source).unwrap().source, "let color = 1;\nlet gray = 0;\n");
+
+        let preprocessor = Preprocessor::new().with_define("GRAY", "");
+        assert_eq!(preprocessor.process("main.wgsl", source).unwrap().source, "let color = 0;\nlet gray = 1;\n");
+
+        // The `#ifdef GRAY` of the inactive `#else` stays inactive
+        let preprocessor = Preprocessor::new().with_define("COLOR", "").with_define("GRAY", "");
+        assert_eq!(preprocessor.process("main.wgsl", source).unwrap().source, "let color = 1;\nlet gray = 1;\n");
+    }
+
+    #[test]
+    fn reports_unbalanced_conditionals() {
+        let error = Preprocessor::new().process("main.wgsl", "let a = 1;\n#endif").unwrap_err();
+        assert_eq!(
+            error,
+            PreprocessError::UnmatchedDirective {
+                location: location("main.wgsl", 2),
+                directive: "endif".to_string()
+            }
+        );
+
+        let error = Preprocessor::new().process("main.wgsl", "#ifdef A\n#else").unwrap_err();
+        assert_eq!(error, PreprocessError::UnterminatedConditional { location: location("main.wgsl", 1) });
+    }
+
+    #[test]
+    fn maps_lines_back_to_their_file() {
+        let preprocessor = Preprocessor::new().with_embedded("common/a.wgsl", "// a\n#ifdef A\nlet a = 1;\n#endif\nlet b = 2;");
+        let shader = preprocessor
+            .process("main.wgsl", "#define A\n#include \"common/a.wgsl\"\nlet main = 0;")
+            .unwrap();
+        assert_eq!(shader.source, "// a\nlet a = 1;\nlet b = 2;\nlet main = 0;\n");
+        assert_eq!(shader.location(1), Some(&location("common/a.wgsl", 1)));
+        assert_eq!(shader.location(2), Some(&location("common/a.wgsl", 3)));
+        assert_eq!(shader.location(3), Some(&location("common/a.wgsl", 5)));
+        assert_eq!(shader.location(4), Some(&location("main.wgsl", 3)));
+        assert_eq!(shader.location(0), None);
+        assert_eq!(shader.location(5), None);
+    }
+
+    #[test]
+    fn includes_files_next_to_the_including_one() {
+        let dir = std::env::temp_dir().join(format!("preprocessor-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("common")).unwrap();
+        fs::write(dir.join("main.wgsl"), "#include \"common/a.wgsl\"\nlet main = 0;").unwrap();
+        fs::write(dir.join("common/a.wgsl"), "#include \"b.wgsl\"\nlet a = 1;").unwrap();
+        fs::write(dir.join("common/b.wgsl"), "let b = 2;").unwrap();
+
+        let shader = Preprocessor::new().process_file(dir.join("main.wgsl"));
+        fs::remove_dir_all(&dir).unwrap();
+        let shader = shader.unwrap();
+        assert_eq!(shader.source, "let b = 2;\nlet a = 1;\nlet main = 0;\n");
+        assert_eq!(
+            shader.files(),
+            [dir.join("main.wgsl"), dir.join("common/a.wgsl"), dir.join("common/b.wgsl")]
+        );
+        assert_eq!(shader.location(1).unwrap().file, dir.join("common/b.wgsl"));
+    }
+}
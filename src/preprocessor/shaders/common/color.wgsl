@@ -0,0 +1,7 @@
+// Rec. 709 luminance weights, for linear RGB.
+const LUMINANCE = vec3<f32>(0.2126, 0.7152, 0.0722);
+
+// Textures hold linear colors, thresholds are easier to tune on perceptual lightness.
+fn lightness(color: vec4<f32>) -> f32 {
+    return pow(dot(color.rgb, LUMINANCE), 1.0 / 2.2);
+}
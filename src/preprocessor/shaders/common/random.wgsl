@@ -0,0 +1,13 @@
+// PCG hash from "Hash Functions for GPU Rendering" (Jarzynski and Olano), integer math only so the
+// same seed gives the same values on every GPU.
+fn pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// A value in [0, 1) for a pixel, e.g. `random_at(id.xy, uniforms.seed)`.
+fn random_at(coords: vec2<u32>, seed: u32) -> f32 {
+    let hash = pcg_hash(coords.x ^ pcg_hash(coords.y ^ pcg_hash(seed)));
+    return f32(hash >> 8u) / 16777216.0;
+}
@@ -0,0 +1,365 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nannou_egui::egui;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::interpolation::{blend, Interpolate};
+
+/// Command line argument of the examples to start from a preset, e.g. `--preset ink.json`.
+pub const PRESET_ARG: &str = "--preset";
+
+const PRESET_EXTENSION: &str = "json";
+
+/// A named look: the parameters of a sketch and the effect they were tuned for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset<T> {
+    /// The effect or shader the parameters apply to, e.g. `xdog`.
+    pub effect: String,
+    pub params: T,
+}
+
+impl<T> Preset<T> {
+    pub fn new(effect: &str, params: T) -> Self {
+        Preset {
+            effect: effect.to_string(),
+            params,
+        }
+    }
+}
+
+impl<T: Serialize> Preset<T> {
+    /// Writes the preset as pretty printed JSON, creating the parent directories if needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PresetError> {
+        let path = path.as_ref();
+        let io_error = |err: std::io::Error| PresetError::Io {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|err| PresetError::Parse {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        fs::write(path, json).map_err(io_error)
+    }
+}
+
+impl<T: DeserializeOwned> Preset<T> {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PresetError> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|err| PresetError::Io {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        serde_json::from_str(&json).map_err(|err| PresetError::Parse {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })
+    }
+
+    /// The preset given with `--preset <path>` on the command line, if any.
+    pub fn from_args() -> Option<Result<Self, PresetError>> {
+        let mut args = std::env::args().skip_while(|arg| arg != PRESET_ARG).skip(1);
+        args.next().map(Self::load)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetError {
+    Io { path: PathBuf, message: String },
+    /// Not valid JSON, or parameters of another sketch.
+    Parse { path: PathBuf, message: String },
+    /// A library preset name that is empty or would leave its directory, e.g. `../ink`.
+    InvalidName { name: String },
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresetError::Io { path, message } => write!(f, "{}: {}", path.display(), message),
+            PresetError::Parse { path, message } => write!(f, "{}: invalid preset: {}", path.display(), message),
+            PresetError::InvalidName { name } => write!(
+                f,
+                "invalid preset name \"{}\", names can't be empty or contain `/`, `\\` or `..`",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+/// The presets saved as `<name>.json` in a directory, with an egui panel to save and load them.
+pub struct PresetLibrary {
+    dir: PathBuf,
+    names: Vec<String>,
+    /// Name typed in the panel for the next save.
+    new_name: String,
+    error: Option<String>,
+}
+
+impl PresetLibrary {
+    /// The directory doesn't need to exist until a preset is saved.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        let mut library = PresetLibrary {
+            dir: dir.into(),
+            names: Vec::new(),
+            new_name: String::new(),
+            error: None,
+        };
+        library.refresh();
+        library
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Names of the presets found by the last `refresh`, sorted.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Where the preset is saved, names with path separators or `..` are rejected so that every
+    /// preset stays in `dir`.
+    pub fn path(&self, name: &str) -> Result<PathBuf, PresetError> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+            return Err(PresetError::InvalidName { name: name.to_string() });
+        }
+        Ok(self.dir.join(format!("{}.{}", name, PRESET_EXTENSION)))
+    }
+
+    /// Lists the directory again, e.g. after presets were added by hand.
+    pub fn refresh(&mut self) {
+        let entries = fs::read_dir(&self.dir).into_iter().flatten().flatten();
+        self.names = entries
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == PRESET_EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect();
+        self.names.sort();
+    }
+
+    /// Saves over any preset with the same name.
+    pub fn save<T: Serialize>(&mut self, name: &str, preset: &Preset<T>) -> Result<(), PresetError> {
+        preset.save(self.path(name)?)?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Result<Preset<T>, PresetError> {
+        Preset::load(self.path(name)?)
+    }
+
+    /// Shows a button per preset and a field to save `current` under a new name.
+    ///
+    /// Returns the preset that was clicked, failures are shown in the panel.
+    pub fn ui<T>(&mut self, ui: &mut egui::Ui, current: &Preset<T>) -> Option<Preset<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let mut loaded = None;
+        for name in self.names.clone() {
+            if ui.button(&name).clicked() {
+                match self.load(&name) {
+                    Ok(preset) => {
+                        self.error = None;
+                        loaded = Some(preset);
+                    }
+                    Err(err) => self.error = Some(err.to_string()),
+                }
+            }
+        }
+        if self.names.is_empty() {
+            ui.label(format!("No presets in {}", self.dir.display()));
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_name);
+            let name = self.new_name.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+                self.error = self.save(&name, current).err().map(|err| err.to_string());
+            }
+        });
+        if ui.button("Refresh").clicked() {
+            self.refresh();
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+        loaded
+    }
+}
+
+/// A crossfader through the parameters of an ordered list of presets of a library.
+///
+/// The position goes from `0` for the first preset to `len - 1` for the last, blending each one
+/// with the next as in `interpolation::blend`. A preset can appear more than once, e.g. to come
+/// back to the first look at the end.
+pub struct PresetMorph<T> {
+    names: Vec<String>,
+    params: Vec<T>,
+    position: f32,
+    error: Option<String>,
+}
+
+impl<T> Default for PresetMorph<T> {
+    fn default() -> Self {
+        PresetMorph {
+            names: Vec::new(),
+            params: Vec::new(),
+            position: 0.0,
+            error: None,
+        }
+    }
+}
+
+impl<T: Interpolate + Clone + DeserializeOwned> PresetMorph<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names of the presets, in the order they are blended.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// From `0` for the first preset to `len - 1` for the last one.
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Clamped to the presets.
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(0.0, self.names.len().saturating_sub(1) as f32);
+    }
+
+    /// Appends a preset after the others.
+    pub fn push(&mut self, name: &str, params: T) {
+        self.names.push(name.to_string());
+        self.params.push(params);
+    }
+
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) {
+        self.names.remove(index);
+        self.params.remove(index);
+        self.set_position(self.position);
+    }
+
+    /// The blend at the current position, once a preset is chosen.
+    pub fn value(&self) -> Option<T> {
+        blend(&self.params, self.position)
+    }
+
+    /// Shows the presets in order with a picker to add one and the crossfader, returns the blend
+    /// when it changes.
+    pub fn ui(&mut self, ui: &mut egui::Ui, library: &PresetLibrary) -> Option<T> {
+        let mut raised = None;
+        let mut removed = None;
+        for (i, name) in self.names.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}. {}", i + 1, name));
+                if i > 0 && ui.small_button("⬆").on_hover_text("Move up").clicked() {
+                    raised = Some(i);
+                }
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        let mut changed = raised.is_some() || removed.is_some();
+        if let Some(i) = raised {
+            self.names.swap(i - 1, i);
+            self.params.swap(i - 1, i);
+        }
+        if let Some(i) = removed {
+            self.remove(i);
+        }
+
+        changed |= self.pick(ui, library);
+        let last = self.names.len().saturating_sub(1) as f32;
+        let slider = egui::Slider::new(&mut self.position, 0.0..=last).text("Position");
+        changed |= ui.add_enabled(self.names.len() > 1, slider).changed();
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+        if changed {
+            self.value()
+        } else {
+            None
+        }
+    }
+
+    /// A picker adding a preset of the library at the end, returns whether one was added.
+    fn pick(&mut self, ui: &mut egui::Ui, library: &PresetLibrary) -> bool {
+        let mut picked = None;
+        egui::ComboBox::from_label("Add")
+            .selected_text("Preset")
+            .show_ui(ui, |ui| {
+                for name in library.names() {
+                    if ui.selectable_label(false, name).clicked() {
+                        picked = Some(name.clone());
+                    }
+                }
+            });
+        let Some(name) = picked else {
+            return false;
+        };
+        match library.load::<T>(&name) {
+            Ok(preset) => {
+                self.push(&name, preset.params);
+                self.error = None;
+                true
+            }
+            Err(err) => {
+                self.error = Some(err.to_string());
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_presets_in_the_library_directory() {
+        let library = PresetLibrary::new("presets");
+        assert_eq!(library.path("ink wash").unwrap(), Path::new("presets/ink wash.json"));
+        assert_eq!(library.path("v1.2").unwrap(), Path::new("presets/v1.2.json"));
+        for name in ["", "../ink", "..", "sub/ink", "sub\\ink", "/etc/ink"] {
+            assert_eq!(library.path(name), Err(PresetError::InvalidName { name: name.to_string() }));
+        }
+    }
+
+    #[test]
+    fn morphs_through_every_preset_in_order() {
+        let mut morph = PresetMorph::new();
+        assert_eq!(morph.value(), None);
+        morph.push("dark", 0.0);
+        assert_eq!(morph.value(), Some(0.0));
+        morph.push("light", 1.0);
+        morph.push("mid", 0.5);
+
+        morph.set_position(0.5);
+        assert_eq!(morph.value(), Some(0.5));
+        morph.set_position(1.5);
+        assert_eq!(morph.value(), Some(0.75));
+        morph.set_position(5.0);
+        assert_eq!(morph.position(), 2.0);
+        assert_eq!(morph.value(), Some(0.5));
+
+        // The position stays within the remaining presets
+        morph.remove(2);
+        assert_eq!(morph.names(), ["dark", "light"]);
+        assert_eq!(morph.position(), 1.0);
+        assert_eq!(morph.value(), Some(1.0));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use nannou_egui::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::interpolation::{step, Interpolate};
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+const PCG_INCREMENT: u64 = 1442695040888963407;
+
+/// A random generator owned by a sketch, so that what it draws can be reproduced from its seed.
+///
+/// The numbers only depend on the seed and on how many were drawn (PCG32, not nannou's thread
+/// local generator). Pass `seed()` to shaders and use `random_at` from `common/random.wgsl` for
+/// noise that is reproducible too. Of the library kernels only the DoG grain draws noise, through
+/// `DogUniforms::seed`, XDoG and FDoG have no random part and take no seed.
+///
+/// Serialized as its seed alone, so a preset restarts the sequence from the beginning.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
+pub struct SketchRng {
+    seed: u32,
+    state: u64,
+}
+
+impl SketchRng {
+    pub fn new(seed: u32) -> Self {
+        let mut rng = SketchRng { seed, state: 0 };
+        rng.reseed(seed);
+        rng
+    }
+
+    /// Seeded from the OS, for sketches that want a different look on every run.
+    pub fn from_entropy() -> Self {
+        Self::new(random_seed())
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Restarts the sequence from a new seed.
+    pub fn reseed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.state = 0;
+        self.next_u32();
+        self.state = self.state.wrapping_add(seed as u64);
+        self.next_u32();
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state.wrapping_mul(PCG_MULTIPLIER).wrapping_add(PCG_INCREMENT);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    /// A value in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// A value in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// A linear RGB color.
+    pub fn color(&mut self) -> [f32; 3] {
+        [self.next_f32(), self.next_f32(), self.next_f32()]
+    }
+
+    /// Shows the seed, editable, and a button to pick a new one. Returns whether it changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.horizontal(|ui| {
+            let mut seed = self.seed;
+            let mut changed = ui.add(egui::DragValue::new(&mut seed).prefix("Seed: ")).changed();
+            if ui.button("New seed").clicked() {
+                seed = random_seed();
+                changed = true;
+            }
+            if changed {
+                self.reseed(seed);
+            }
+            changed
+        })
+        .inner
+    }
+}
+
+impl From<u32> for SketchRng {
+    fn from(seed: u32) -> Self {
+        SketchRng::new(seed)
+    }
+}
+
+impl From<SketchRng> for u32 {
+    fn from(rng: SketchRng) -> Self {
+        rng.seed
+    }
+}
+
+/// Seeds can't be blended, the generator switches halfway.
+impl Interpolate for SketchRng {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        step(self, other, t)
+    }
+}
+
+/// A seed that differs on every call, from the OS randomness of the standard library.
+pub fn random_seed() -> u32 {
+    RandomState::new().build_hasher().finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(rng: &mut SketchRng) -> Vec<u32> {
+        (0..16).map(|_| rng.next_u32()).collect()
+    }
+
+    #[test]
+    fn repeats_the_sequence_of_a_seed() {
+        let first = draw(&mut SketchRng::new(42));
+        assert_eq!(first, draw(&mut SketchRng::new(42)));
+        assert_ne!(first, draw(&mut SketchRng::new(43)));
+        assert_ne!(first[..8], first[8..]);
+    }
+
+    #[test]
+    fn restarts_the_sequence_when_reseeded() {
+        let mut rng = SketchRng::new(7);
+        let first = draw(&mut rng);
+        rng.reseed(7);
+        assert_eq!(draw(&mut rng), first);
+        assert_eq!(rng.seed(), 7);
+
+        rng.reseed(8);
+        assert_eq!(rng, SketchRng::new(8));
+    }
+
+    #[test]
+    fn draws_floats_below_one() {
+        let mut rng = SketchRng::new(0);
+        for _ in 0..10_000 {
+            let value = rng.next_f32();
+            assert!((0.0..1.0).contains(&value), "{}", value);
+        }
+        for _ in 0..1000 {
+            let value = rng.range(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn serializes_as_its_seed() {
+        let mut rng = SketchRng::new(1234);
+        rng.next_u32();
+        assert_eq!(serde_json::to_string(&rng).unwrap(), "1234");
+
+        let restored: SketchRng = serde_json::from_str("1234").unwrap();
+        assert_eq!(restored, SketchRng::new(1234));
+        assert!(serde_json::from_str::<SketchRng>("{\"seed\": 1234}").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use color_quant::NeuQuant;
+use nannou::image::RgbaImage;
+
+use crate::recording::RecordingError;
+
+/// Sampling factor of NeuQuant, from 1 (best and slowest) to 30.
+const QUANTIZER_SAMPLING: i32 = 10;
+
+/// Writes frames into a GIF that loops forever, each with its own palette of 256 colors.
+///
+/// GIF delays are in hundredths of a second, they alternate to keep the average frame rate (e.g.
+/// 3 and 4 at 30 fps). Most viewers slow down delays under 2, so GIFs above 50 fps play slower.
+pub struct GifWriter {
+    path: PathBuf,
+    encoder: gif::Encoder<BufWriter<File>>,
+    size: [u16; 2],
+    fps: u32,
+    dither: bool,
+    frame: u32,
+}
+
+impl GifWriter {
+    pub fn create(path: &Path, size: [u32; 2], fps: u32, dither: bool) -> Result<Self, RecordingError> {
+        let error = |message: String| RecordingError::Io {
+            path: path.to_path_buf(),
+            message,
+        };
+        let [width, height] = size;
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(error(format!("GIFs are at most {} pixels wide and high", u16::MAX)));
+        };
+        let file = File::create(path).map_err(|err| error(err.to_string()))?;
+        let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[]).map_err(|err| error(err.to_string()))?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|err| error(err.to_string()))?;
+        Ok(GifWriter {
+            path: path.to_path_buf(),
+            encoder,
+            size: [width, height],
+            fps,
+            dither,
+            frame: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> Result<(), RecordingError> {
+        let (palette, indices) = quantize(image, self.dither);
+        let frame = gif::Frame {
+            width: self.size[0],
+            height: self.size[1],
+            delay: gif_delay(self.frame, self.fps),
+            palette: Some(palette),
+            buffer: Cow::Owned(indices),
+            ..Default::default()
+        };
+        self.encoder.write_frame(&frame).map_err(|err| RecordingError::Io {
+            path: self.path.clone(),
+            message: err.to_string(),
+        })?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Writes the end of the file.
+    pub fn finish(self) -> Result<(), RecordingError> {
+        self.encoder.into_inner().map(drop).map_err(|err| RecordingError::Io {
+            path: self.path,
+            message: err.to_string(),
+        })
+    }
+}
+
+/// Delay of `frame` in hundredths of a second, so that the frames up to it end at the same time as
+/// they would at `fps`.
+fn gif_delay(frame: u32, fps: u32) -> u16 {
+    let fps = fps as u64;
+    let centiseconds = |frame: u32| (frame as u64 * 200 + fps) / (2 * fps);
+    (centiseconds(frame + 1) - centiseconds(frame)).min(u16::MAX as u64) as u16
+}
+
+/// Writes frames into an APNG that loops forever, in full color.
+///
+/// The number of frames is part of the header, a file finished early is reported as an error.
+pub struct ApngWriter {
+    path: PathBuf,
+    writer: png::Writer<BufWriter<File>>,
+    frames: u32,
+    written: u32,
+}
+
+impl ApngWriter {
+    pub fn create(path: &Path, size: [u32; 2], fps: u32, frames: u32) -> Result<Self, RecordingError> {
+        let error = |message: String| RecordingError::Io {
+            path: path.to_path_buf(),
+            message,
+        };
+        let file = File::create(path).map_err(|err| error(err.to_string()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), size[0], size[1]);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        // Zero plays is forever
+        encoder.set_animated(frames, 0).map_err(|err| error(err.to_string()))?;
+        let fps = u16::try_from(fps).map_err(|_| error(format!("APNGs are at most {} fps", u16::MAX)))?;
+        encoder.set_frame_delay(1, fps).map_err(|err| error(err.to_string()))?;
+        let writer = encoder.write_header().map_err(|err| error(err.to_string()))?;
+        Ok(ApngWriter {
+            path: path.to_path_buf(),
+            writer,
+            frames,
+            written: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> Result<(), RecordingError> {
+        self.writer.write_image_data(image.as_raw()).map_err(|err| RecordingError::Io {
+            path: self.path.clone(),
+            message: err.to_string(),
+        })?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Writes the end of the file, fails if fewer frames than announced were written.
+    pub fn finish(self) -> Result<(), RecordingError> {
+        let error = |message: String| RecordingError::Io {
+            path: self.path.clone(),
+            message,
+        };
+        if self.written < self.frames {
+            return Err(error(format!("stopped after {} of {} frames", self.written, self.frames)));
+        }
+        self.writer.finish().map_err(|err| error(err.to_string()))
+    }
+}
+
+/// Reduces an opaque image to a palette of at most 256 colors, for GIF. Returns the palette as
+/// RGB triplets and the palette index of each pixel.
+///
+/// Images that already have at most 256 colors keep them exactly. Otherwise with `dither`, the
+/// error of each pixel is spread to its neighbours (Floyd-Steinberg), trading the banding of
+/// gradients for a fine noise.
+pub fn quantize(image: &RgbaImage, dither: bool) -> (Vec<u8>, Vec<u8>) {
+    if let Some(exact) = exact_palette(image) {
+        return exact;
+    }
+
+    let quantizer = NeuQuant::new(QUANTIZER_SAMPLING, 256, image.as_raw());
+    let palette = quantizer.color_map_rgb();
+    if !dither {
+        let indices = image.pixels().map(|pixel| quantizer.index_of(&pixel.0) as u8).collect();
+        return (palette, indices);
+    }
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut pixels: Vec<[f32; 3]> = image
+        .pixels()
+        .map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32])
+        .collect();
+    let mut indices = Vec::with_capacity(pixels.len());
+    for y in 0..height {
+        for x in 0..width {
+            let wanted = pixels[y * width + x];
+            let [r, g, b] = wanted.map(|channel| channel.round().clamp(0.0, 255.0) as u8);
+            let index = quantizer.index_of(&[r, g, b, 255]);
+            indices.push(index as u8);
+
+            let chosen = &palette[index * 3..index * 3 + 3];
+            let error: [f32; 3] = std::array::from_fn(|channel| wanted[channel] - chosen[channel] as f32);
+            let mut spread = |x: usize, y: usize, weight: f32| {
+                if x < width && y < height {
+                    let pixel = &mut pixels[y * width + x];
+                    for channel in 0..3 {
+                        pixel[channel] += error[channel] * weight;
+                    }
+                }
+            };
+            spread(x + 1, y, 7.0 / 16.0);
+            spread(x.wrapping_sub(1), y + 1, 3.0 / 16.0);
+            spread(x, y + 1, 5.0 / 16.0);
+            spread(x + 1, y + 1, 1.0 / 16.0);
+        }
+    }
+    (palette, indices)
+}
+
+/// The palette of the colors of the image and their indices, if there are at most 256 of them.
+fn exact_palette(image: &RgbaImage) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity(image.len() / 4);
+    for pixel in image.pixels() {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match colors.get(&color) {
+            Some(&index) => index,
+            None => {
+                let index = u8::try_from(colors.len()).ok()?;
+                colors.insert(color, index);
+                palette.extend_from_slice(&color);
+                index
+            }
+        };
+        indices.push(index);
+    }
+    Some((palette, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use nannou::image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn alternates_delays_to_keep_the_frame_rate() {
+        let delays: Vec<u16> = (0..6).map(|frame| gif_delay(frame, 30)).collect();
+        assert_eq!(delays, [3, 4, 3, 3, 4, 3]);
+        assert!((0..100).all(|frame| gif_delay(frame, 50) == 2));
+
+        for fps in [1, 7, 24, 30, 60, 240] {
+            let frames = fps * 10;
+            let total: u32 = (0..frames).map(|frame| gif_delay(frame, fps) as u32).sum();
+            assert_eq!(total, frames * 100 / fps, "{} fps", fps);
+        }
+    }
+
+    #[test]
+    fn keeps_the_delays_of_long_recordings() {
+        // Past 655 s the total of the delays doesn't fit in 16 bits anymore
+        let frames = 60 * 60 * 30;
+        assert!((frames - 30..frames).all(|frame| (3..=4).contains(&gif_delay(frame, 30))));
+        assert_eq!(gif_delay(0, 1), 100);
+    }
+
+    fn image_with_colors(colors: u32) -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |x, y| {
+            let color = (x * 7 + y * 13) % colors;
+            Rgba([(color * 37 % 256) as u8, (color * 101 % 256) as u8, (color * 13 % 256) as u8, 255])
+        })
+    }
+
+    fn dequantize(palette: &[u8], indices: &[u8]) -> Vec<[u8; 3]> {
+        let index = |i: u8| i as usize * 3;
+        indices.iter().map(|&i| [palette[index(i)], palette[index(i) + 1], palette[index(i) + 2]]).collect()
+    }
+
+    #[test]
+    fn quantizes_to_at_most_256_colors() {
+        let image = RgbaImage::from_fn(256, 256, |x, y| Rgba([x as u8, y as u8, (x ^ y) as u8, 255]));
+        for dither in [false, true] {
+            let (palette, indices) = quantize(&image, dither);
+            assert!(palette.len() <= 256 * 3 && palette.len() % 3 == 0);
+            assert_eq!(indices.len(), 256 * 256);
+            assert!(indices.iter().all(|&index| (index as usize) * 3 < palette.len()));
+        }
+    }
+
+    #[test]
+    fn keeps_images_with_few_colors_intact() {
+        for colors in [1, 2, 16, 256] {
+            let image = image_with_colors(colors);
+            let expected: Vec<[u8; 3]> = image.pixels().map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+            for dither in [false, true] {
+                let (palette, indices) = quantize(&image, dither);
+                assert!(dequantize(&palette, &indices) == expected, "{} colors, dither: {}", colors, dither);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_apng_frame_rates_above_the_header_limit() {
+        let path = std::env::temp_dir().join(format!("animation-test-{}.png", std::process::id()));
+        let error = ApngWriter::create(&path, [4, 4], u16::MAX as u32 + 1, 2).err().unwrap();
+        assert!(error.to_string().contains("fps"), "{}", error);
+        assert!(ApngWriter::create(&path, [4, 4], u16::MAX as u32, 2).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
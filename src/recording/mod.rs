@@ -0,0 +1,437 @@
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use nannou::image::{ImageFormat, RgbaImage};
+use nannou_egui::egui;
+
+use crate::recording::animation::{ApngWriter, GifWriter};
+
+pub mod animation;
+
+/// Encodes the frames into an H.264 video next to the PNG sequence, with `ffmpeg` on the `PATH`.
+pub const FFMPEG_MP4: &str =
+    "ffmpeg -y -f rawvideo -pix_fmt rgba -s {width}x{height} -r {fps} -i - -pix_fmt yuv420p -crf 18 recording.mp4";
+
+/// What the frames of a recording are written as.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RecordingFormat {
+    /// One numbered PNG per frame.
+    #[default]
+    PngSequence,
+    /// A single `recording.gif`, for short loops.
+    Gif,
+    /// A single `recording.png`, animated and in full color.
+    Apng,
+}
+
+/// What to record and where, see `Recorder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingSettings {
+    /// Frames per second of the virtual clock, and of the video.
+    pub fps: u32,
+    /// Length of the recording in frames.
+    pub frames: u32,
+    /// Resolution of the frames, independent of the window size.
+    pub size: [u32; 2],
+    /// Where the frames are written, created if needed.
+    pub directory: PathBuf,
+    pub format: RecordingFormat,
+    /// Dithers the palette of GIF frames.
+    pub dither: bool,
+    /// Command that gets the raw RGBA frames on its standard input, run in `directory`.
+    ///
+    /// Split on whitespace, without a shell. `{width}`, `{height}` and `{fps}` are replaced by
+    /// the settings, e.g. `FFMPEG_MP4`.
+    pub encoder: Option<String>,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        RecordingSettings {
+            fps: 60,
+            frames: 600,
+            size: [1920, 1080],
+            directory: PathBuf::from("recordings"),
+            format: RecordingFormat::PngSequence,
+            dither: true,
+            encoder: None,
+        }
+    }
+}
+
+impl RecordingSettings {
+    pub fn duration(&self) -> f32 {
+        self.frames as f32 / self.fps as f32
+    }
+
+    /// The file of the GIF or APNG formats.
+    pub fn animation_path(&self) -> Option<PathBuf> {
+        match self.format {
+            RecordingFormat::PngSequence => None,
+            RecordingFormat::Gif => Some(self.directory.join("recording.gif")),
+            RecordingFormat::Apng => Some(self.directory.join("recording.png")),
+        }
+    }
+
+    /// The encoder command with its placeholders replaced, as program and arguments.
+    pub fn encoder_args(&self) -> Option<Vec<String>> {
+        let command = self.encoder.as_ref()?;
+        let args: Vec<String> = command
+            .split_whitespace()
+            .map(|arg| {
+                arg.replace("{width}", &self.size[0].to_string())
+                    .replace("{height}", &self.size[1].to_string())
+                    .replace("{fps}", &self.fps.to_string())
+            })
+            .collect();
+        if args.is_empty() {
+            None
+        } else {
+            Some(args)
+        }
+    }
+
+    /// Shows a widget per setting. Returns whether any of them changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            changed |= ui.add(egui::DragValue::new(&mut self.size[0]).clamp_range(1..=16384).suffix(" px")).changed();
+            ui.label("x");
+            changed |= ui.add(egui::DragValue::new(&mut self.size[1]).clamp_range(1..=16384).suffix(" px")).changed();
+        });
+        ui.horizontal(|ui| {
+            changed |= ui.add(egui::DragValue::new(&mut self.fps).clamp_range(1..=240).suffix(" fps")).changed();
+            changed |= ui.add(egui::DragValue::new(&mut self.frames).clamp_range(1..=u32::MAX).suffix(" frames")).changed();
+        });
+        ui.label(format!("{:.2} seconds", self.duration()));
+
+        ui.horizontal(|ui| {
+            for (format, label) in [
+                (RecordingFormat::PngSequence, "PNG sequence"),
+                (RecordingFormat::Gif, "GIF"),
+                (RecordingFormat::Apng, "APNG"),
+            ] {
+                changed |= ui.selectable_value(&mut self.format, format, label).changed();
+            }
+        });
+        if self.format == RecordingFormat::Gif {
+            changed |= ui.checkbox(&mut self.dither, "Dither").changed();
+        }
+
+        ui.label("Directory:");
+        let mut directory = self.directory.display().to_string();
+        if ui.text_edit_singleline(&mut directory).changed() {
+            self.directory = PathBuf::from(directory);
+            changed = true;
+        }
+
+        ui.label("Encoder command (optional):");
+        let mut encoder = self.encoder.clone().unwrap_or_default();
+        ui.horizontal(|ui| {
+            let mut encoder_changed = ui.text_edit_singleline(&mut encoder).changed();
+            if ui.button("ffmpeg").clicked() {
+                encoder = FFMPEG_MP4.to_string();
+                encoder_changed = true;
+            }
+            if encoder_changed {
+                self.encoder = Some(encoder).filter(|command| !command.trim().is_empty());
+                changed = true;
+            }
+        });
+        changed
+    }
+}
+
+/// Records an animation frame by frame, on a virtual clock rather than the real one.
+///
+/// The sketch takes its time from `time()` instead of `app.time` while recording, renders the
+/// frame offscreen at `settings.size` and hands it to `write_frame`, which advances the clock by
+/// exactly `1 / fps`. However long a frame takes to render, the output has the same frames.
+pub struct Recorder {
+    settings: RecordingSettings,
+    frame: u32,
+    /// Taken once finished, like `encoder`.
+    output: Option<Output>,
+    encoder: Option<Child>,
+}
+
+enum Output {
+    PngSequence,
+    Gif(GifWriter),
+    Apng(ApngWriter),
+}
+
+impl Recorder {
+    /// Creates the directory and the animated file, and starts the encoder, if any. Fails without
+    /// creating anything if `fps` or `frames` is `0`.
+    pub fn start(settings: RecordingSettings) -> Result<Self, RecordingError> {
+        if settings.fps == 0 {
+            return Err(RecordingError::Settings {
+                message: "the frame rate must be at least 1 fps".to_string(),
+            });
+        }
+        if settings.frames == 0 {
+            return Err(RecordingError::Settings {
+                message: "a recording needs at least one frame".to_string(),
+            });
+        }
+        fs::create_dir_all(&settings.directory).map_err(|err| RecordingError::Io {
+            path: settings.directory.clone(),
+            message: err.to_string(),
+        })?;
+        let path = settings.animation_path().unwrap_or_default();
+        let output = match settings.format {
+            RecordingFormat::PngSequence => Output::PngSequence,
+            RecordingFormat::Gif => Output::Gif(GifWriter::create(&path, settings.size, settings.fps, settings.dither)?),
+            RecordingFormat::Apng => Output::Apng(ApngWriter::create(&path, settings.size, settings.fps, settings.frames)?),
+        };
+        let encoder = match settings.encoder_args() {
+            Some(args) => {
+                let child = Command::new(&args[0])
+                    .args(&args[1..])
+                    .current_dir(&settings.directory)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|err| RecordingError::Encoder {
+                        command: args.join(" "),
+                        message: err.to_string(),
+                    })?;
+                Some(child)
+            }
+            None => None,
+        };
+        Ok(Recorder {
+            settings,
+            frame: 0,
+            output: Some(output),
+            encoder,
+        })
+    }
+
+    pub fn settings(&self) -> &RecordingSettings {
+        &self.settings
+    }
+
+    /// Index of the next frame to write.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Seconds on the virtual clock for the next frame, starting from `0`.
+    pub fn time(&self) -> f32 {
+        (self.frame as f64 / self.settings.fps as f64) as f32
+    }
+
+    /// From `0` at the start to `1` once every frame is written.
+    pub fn progress(&self) -> f32 {
+        self.frame as f32 / self.settings.frames as f32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.settings.frames
+    }
+
+    /// Path of the PNG of a frame, numbered so that the files sort in order.
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        let digits = self.settings.frames.saturating_sub(1).to_string().len().max(5);
+        self.settings.directory.join(format!("frame_{:0digits$}.png", frame, digits = digits))
+    }
+
+    /// Writes the next frame and advances the clock. The output and the encoder are closed after
+    /// the last one.
+    pub fn write_frame(&mut self, image: &RgbaImage) -> Result<(), RecordingError> {
+        if self.is_finished() {
+            return Ok(());
+        }
+        let size = [image.width(), image.height()];
+        if size != self.settings.size {
+            return Err(RecordingError::Size {
+                expected: self.settings.size,
+                actual: size,
+            });
+        }
+
+        match &mut self.output {
+            Some(Output::PngSequence) => {
+                let path = self.frame_path(self.frame);
+                image.save_with_format(&path, ImageFormat::Png).map_err(|err| RecordingError::Io {
+                    path: path.clone(),
+                    message: err.to_string(),
+                })?;
+            }
+            Some(Output::Gif(gif)) => gif.write_frame(image)?,
+            Some(Output::Apng(apng)) => apng.write_frame(image)?,
+            None => {}
+        }
+        if let Some(stdin) = self.encoder.as_mut().and_then(|encoder| encoder.stdin.as_mut()) {
+            stdin.write_all(image.as_raw()).map_err(|err| RecordingError::Encoder {
+                command: self.settings.encoder.clone().unwrap_or_default(),
+                message: err.to_string(),
+            })?;
+        }
+
+        self.frame += 1;
+        if self.is_finished() {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    /// Stops early, keeping the frames written so far. The encoder finishes with those, an APNG
+    /// can't as its number of frames is already written.
+    pub fn finish(mut self) -> Result<(), RecordingError> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<(), RecordingError> {
+        let output = match self.output.take() {
+            Some(Output::Gif(gif)) => gif.finish(),
+            Some(Output::Apng(apng)) => apng.finish(),
+            _ => Ok(()),
+        };
+        // The encoder is closed even if the output failed
+        self.close_encoder().and(output)
+    }
+
+    /// Closes the standard input of the encoder and waits for it to finish writing.
+    fn close_encoder(&mut self) -> Result<(), RecordingError> {
+        let Some(mut encoder) = self.encoder.take() else {
+            return Ok(());
+        };
+        drop(encoder.stdin.take());
+        let command = self.settings.encoder.clone().unwrap_or_default();
+        let status = encoder.wait().map_err(|err| RecordingError::Encoder {
+            command: command.clone(),
+            message: err.to_string(),
+        })?;
+        if !status.success() {
+            return Err(RecordingError::Encoder {
+                command,
+                message: status.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Lets the outputs be finalized, errors are only reported by `finish`.
+        self.close().ok();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingError {
+    Io { path: PathBuf, message: String },
+    /// The encoder couldn't be started, stopped reading its input or exited with an error.
+    Encoder { command: String, message: String },
+    /// A frame wasn't rendered at the resolution of the recording.
+    Size { expected: [u32; 2], actual: [u32; 2] },
+    /// The settings can't describe a recording, e.g. `0` fps.
+    Settings { message: String },
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordingError::Io { path, message } => write!(f, "{}: {}", path.display(), message),
+            RecordingError::Encoder { command, message } => write!(f, "encoder `{}`: {}", command, message),
+            RecordingError::Size { expected, actual } => write!(
+                f,
+                "frame is {}x{}, the recording is {}x{}",
+                actual[0], actual[1], expected[0], expected[1]
+            ),
+            RecordingError::Settings { message } => write!(f, "invalid recording settings: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+/// Where sketches save the preset of a recording, next to its frames, so that it can be rendered
+/// again with the same parameters and seed.
+pub fn preset_path(directory: &Path) -> PathBuf {
+    directory.join("preset.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings recording PNGs into a directory of their own, removed by the caller.
+    fn settings(name: &str, fps: u32, frames: u32) -> RecordingSettings {
+        RecordingSettings {
+            fps,
+            frames,
+            size: [2, 2],
+            directory: std::env::temp_dir().join(format!("recording-test-{}-{}", name, std::process::id())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn advances_the_clock_by_one_frame_per_write() {
+        let settings = settings("clock", 30, 4);
+        let directory = settings.directory.clone();
+        let mut recorder = Recorder::start(settings).unwrap();
+        let image = RgbaImage::new(2, 2);
+        let mut times = Vec::new();
+        while !recorder.is_finished() {
+            times.push(recorder.time());
+            recorder.write_frame(&image).unwrap();
+        }
+        assert_eq!(times, [0.0, 1.0 / 30.0, 2.0 / 30.0, 3.0 / 30.0]);
+        assert_eq!(recorder.progress(), 1.0);
+        assert!(recorder.frame_path(3).is_file());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_recordings_without_frames() {
+        for (fps, frames) in [(0, 10), (30, 0)] {
+            let settings = settings("empty", fps, frames);
+            let error = Recorder::start(settings.clone()).err().unwrap();
+            assert!(matches!(error, RecordingError::Settings { .. }), "{}", error);
+            assert!(!settings.directory.exists());
+        }
+    }
+
+    #[test]
+    fn numbers_frames_so_they_sort_in_order() {
+        let name = |frames: u32, frame: u32| {
+            // Not started, so nothing is written
+            let recorder = Recorder {
+                settings: settings("names", 60, frames),
+                frame: 0,
+                output: None,
+                encoder: None,
+            };
+            recorder.frame_path(frame).file_name().unwrap().to_string_lossy().into_owned()
+        };
+        assert_eq!(name(600, 0), "frame_00000.png");
+        assert_eq!(name(600, 599), "frame_00599.png");
+        assert_eq!(name(1_000_001, 42), "frame_0000042.png");
+        assert_eq!(name(1_000_001, 1_000_000), "frame_1000000.png");
+    }
+
+    #[test]
+    fn substitutes_the_encoder_placeholders() {
+        let mut settings = settings("encoder", 24, 10);
+        settings.size = [1280, 720];
+        assert_eq!(settings.encoder_args(), None);
+
+        settings.encoder = Some(FFMPEG_MP4.to_string());
+        let args = settings.encoder_args().unwrap();
+        assert_eq!(args[0], "ffmpeg");
+        assert!(args.windows(2).any(|pair| pair == ["-s", "1280x720"]), "{:?}", args);
+        assert!(args.windows(2).any(|pair| pair == ["-r", "24"]), "{:?}", args);
+        assert!(!args.iter().any(|arg| arg.contains('{')), "{:?}", args);
+
+        settings.encoder = Some("  ".to_string());
+        assert_eq!(settings.encoder_args(), None);
+    }
+}
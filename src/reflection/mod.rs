@@ -0,0 +1,866 @@
+use std::fmt;
+use std::num::NonZeroU64;
+
+use nannou::wgpu;
+
+/// The resource bound to a WGSL variable, see `ShaderReflection::bind`.
+#[derive(Copy, Clone)]
+pub enum BindResource<'a> {
+    /// A uniform or storage buffer.
+    Buffer(&'a wgpu::Buffer),
+    /// A sampled or storage texture.
+    TextureView(&'a wgpu::TextureView),
+    Sampler(&'a wgpu::Sampler),
+}
+
+impl BindResource<'_> {
+    fn describe(&self) -> Resource {
+        match self {
+            BindResource::Buffer(buffer) => Resource::Buffer { size: buffer.size() },
+            BindResource::TextureView(view) => Resource::Texture {
+                sample_type: view.sample_type(),
+                dimension: view.dimension(),
+                format: view.format(),
+            },
+            BindResource::Sampler(_) => Resource::Sampler,
+        }
+    }
+}
+
+/// What `check_resource` needs to know about a `BindResource`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Resource {
+    Buffer {
+        size: u64,
+    },
+    Texture {
+        sample_type: wgpu::TextureSampleType,
+        dimension: wgpu::TextureViewDimension,
+        format: wgpu::TextureFormat,
+    },
+    Sampler,
+}
+
+impl Resource {
+    fn kind(&self) -> &'static str {
+        match self {
+            Resource::Buffer { .. } => "a buffer",
+            Resource::Texture { .. } => "a texture view",
+            Resource::Sampler => "a sampler",
+        }
+    }
+}
+
+/// A resource declared with `@group(g) @binding(b) var ...` in a shader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub name: String,
+    pub group: u32,
+    pub binding: u32,
+    /// The stages of the entry points of the shader.
+    pub visibility: wgpu::ShaderStages,
+    pub ty: wgpu::BindingType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectionError {
+    /// The WGSL doesn't parse, `message` is the full report.
+    Parse { message: String },
+    /// A resource type that bind group layouts can't describe, like binding arrays.
+    UnsupportedBinding { name: String },
+}
+
+impl fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReflectionError::Parse { message } => write!(f, "{}", message),
+            ReflectionError::UnsupportedBinding { name } => write!(f, "unsupported resource type for `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for ReflectionError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingError {
+    /// No variable of the shader has this name.
+    UnknownBinding { name: String },
+    /// A variable of the shader didn't get a resource.
+    MissingResource { name: String },
+    /// `bind_in_order` got a different number of resources than the shader has bindings.
+    ResourceCount { expected: usize, found: usize },
+    /// The resource can't be bound to the variable, e.g. a buffer for a texture.
+    TypeMismatch {
+        name: String,
+        expected: String,
+        found: String,
+    },
+    /// The buffer bound to a uniform doesn't have the size of its struct, usually because the
+    /// Rust type and the WGSL struct declare different fields.
+    BufferSize { name: String, expected: u64, found: u64 },
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BindingError::UnknownBinding { name } => write!(f, "the shader has no binding named `{}`", name),
+            BindingError::MissingResource { name } => write!(f, "no resource given for the binding `{}`", name),
+            BindingError::ResourceCount { expected, found } => write!(
+                f,
+                "the shader declares {} bindings but got {} resources",
+                expected, found
+            ),
+            BindingError::TypeMismatch { name, expected, found } => write!(
+                f,
+                "the binding `{}` expects {} but got {}",
+                name, expected, found
+            ),
+            BindingError::BufferSize { name, expected, found } => write!(
+                f,
+                "the uniform `{}` is {} bytes in the shader but its buffer is {} bytes",
+                name, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BindingError {}
+
+/// The bindings declared by one or more WGSL modules, to create the bind group layouts and bind
+/// groups matching them instead of writing them by hand.
+///
+/// ```ignore
+/// let reflection = ShaderReflection::from_wgsl(source)?;
+/// let bindings = reflection.bind(device, &[
+///     ("uniforms", BindResource::Buffer(uniforms.buffer())),
+///     ("inTexture", BindResource::TextureView(&input_view)),
+///     ("outTexture", BindResource::TextureView(&output_view)),
+/// ])?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderReflection {
+    /// Sorted by group and binding.
+    bindings: Vec<ReflectedBinding>,
+    /// The `(sampler, texture)` pairs of the `textureSample*` calls on global variables.
+    samples: Vec<(String, String)>,
+}
+
+impl ShaderReflection {
+    pub fn from_wgsl(wgsl: &str) -> Result<Self, ReflectionError> {
+        let module = naga::front::wgsl::parse_str(wgsl).map_err(|err| ReflectionError::Parse {
+            message: err.emit_to_string(wgsl),
+        })?;
+
+        let mut visibility = wgpu::ShaderStages::NONE;
+        for entry_point in &module.entry_points {
+            visibility |= match entry_point.stage {
+                naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+            };
+        }
+
+        let mut bindings = Vec::new();
+        for (_, variable) in module.global_variables.iter() {
+            let Some(resource) = &variable.binding else {
+                continue;
+            };
+            let name = variable.name.clone().unwrap_or_default();
+            let inner = &module.types[variable.ty].inner;
+            let ty = binding_type(inner, variable.space, inner.size(module.to_ctx()))
+                .ok_or_else(|| ReflectionError::UnsupportedBinding { name: name.clone() })?;
+            bindings.push(ReflectedBinding {
+                name,
+                group: resource.group,
+                binding: resource.binding,
+                visibility,
+                ty,
+            });
+        }
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+
+        let mut samples = Vec::new();
+        let functions = module.functions.iter().map(|(_, function)| function);
+        for function in functions.chain(module.entry_points.iter().map(|entry_point| &entry_point.function)) {
+            let global_name = |expression| match function.expressions[expression] {
+                naga::Expression::GlobalVariable(handle) => module.global_variables[handle].name.clone(),
+                _ => None,
+            };
+            for (_, expression) in function.expressions.iter() {
+                if let naga::Expression::ImageSample { image, sampler, .. } = *expression {
+                    if let (Some(sampler), Some(image)) = (global_name(sampler), global_name(image)) {
+                        if !samples.contains(&(sampler.clone(), image.clone())) {
+                            samples.push((sampler, image));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(ShaderReflection { bindings, samples })
+    }
+
+    /// Reflects the merged `sources` and creates their bindings with `bind`, for the pipelines built
+    /// from WGSL that was just given to `create_shader_module`. Panics with `context` on failure.
+    ///
+    /// Invalid WGSL already made `create_shader_module` fail, so a reflection error can only be
+    /// reached inside an error scope like the one of `HotReload::update`, which reports the panic.
+    /// Binding errors are mistakes of the calling code.
+    pub fn bind_or_panic(
+        sources: &[&str],
+        context: &str,
+        bind: impl FnOnce(ShaderReflection) -> Result<Bindings, BindingError>,
+    ) -> Bindings {
+        let mut reflection = ShaderReflection::default();
+        for source in sources {
+            let reflected = ShaderReflection::from_wgsl(source).unwrap_or_else(|err| panic!("{}: {}", context, err));
+            reflection = reflection.merge(reflected);
+        }
+        bind(reflection).unwrap_or_else(|err| panic!("{}: {}", context, err))
+    }
+
+    /// Adds the bindings of another stage, e.g. the fragment shader of a render pipeline whose
+    /// vertex shader is in a different module. Bindings present in both are visible to both.
+    pub fn merge(mut self, other: ShaderReflection) -> Self {
+        for binding in other.bindings {
+            let existing = self
+                .bindings
+                .iter_mut()
+                .find(|existing| existing.group == binding.group && existing.binding == binding.binding);
+            match existing {
+                Some(existing) => existing.visibility |= binding.visibility,
+                None => self.bindings.push(binding),
+            }
+        }
+        self.bindings.sort_by_key(|binding| (binding.group, binding.binding));
+        for sample in other.samples {
+            if !self.samples.contains(&sample) {
+                self.samples.push(sample);
+            }
+        }
+        self
+    }
+
+    /// Every binding, sorted by group and binding.
+    pub fn bindings(&self) -> &[ReflectedBinding] {
+        &self.bindings
+    }
+
+    pub fn binding(&self, name: &str) -> Option<&ReflectedBinding> {
+        self.bindings.iter().find(|binding| binding.name == name)
+    }
+
+    /// Number of bind groups of the pipeline layout, including the unused ones below the last.
+    pub fn group_count(&self) -> u32 {
+        self.bindings.last().map_or(0, |binding| binding.group + 1)
+    }
+
+    /// Creates the bind group layouts and bind groups for the given resources, each one named
+    /// after the WGSL variable it is bound to. Every binding needs a resource.
+    ///
+    /// Float textures that can't be filtered (like `rgba32float`) get a non filtering layout
+    /// entry, WGSL doesn't tell them apart. So do the samplers the shader samples them with, which
+    /// must then use `FilterMode::Nearest`.
+    pub fn bind(&self, device: &wgpu::Device, resources: &[(&str, BindResource)]) -> Result<Bindings, BindingError> {
+        let described: Vec<_> = resources.iter().map(|(name, resource)| (*name, resource.describe())).collect();
+        let group_entries = self.layout_entries(&described)?;
+
+        let mut layouts = Vec::new();
+        let mut groups = Vec::new();
+        for (group, layout_entries) in group_entries.iter().enumerate() {
+            let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("reflected-bind-group-layout"),
+                entries: layout_entries,
+            });
+            let entries: Vec<_> = self
+                .bindings
+                .iter()
+                .filter(|binding| binding.group == group as u32)
+                .filter_map(|binding| {
+                    let (_, resource) = resources.iter().find(|(name, _)| *name == binding.name)?;
+                    Some(wgpu::BindGroupEntry {
+                        binding: binding.binding,
+                        resource: match resource {
+                            BindResource::Buffer(buffer) => buffer.as_entire_binding(),
+                            BindResource::TextureView(view) => wgpu::BindingResource::TextureView(view),
+                            BindResource::Sampler(sampler) => wgpu::BindingResource::Sampler(sampler),
+                        },
+                    })
+                })
+                .collect();
+            groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("reflected-bind-group"),
+                layout: &layout,
+                entries: &entries,
+            }));
+            layouts.push(layout);
+        }
+        Ok(Bindings { layouts, groups })
+    }
+
+    /// The layout entries of each group for the given resources, or why they can't be bound.
+    fn layout_entries(&self, resources: &[(&str, Resource)]) -> Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>, BindingError> {
+        for (name, _) in resources {
+            if self.binding(name).is_none() {
+                return Err(BindingError::UnknownBinding { name: name.to_string() });
+            }
+        }
+
+        let mut groups = Vec::new();
+        for group in 0..self.group_count() {
+            let mut entries = Vec::new();
+            for binding in self.bindings.iter().filter(|binding| binding.group == group) {
+                let resource = resources
+                    .iter()
+                    .find(|(name, _)| *name == binding.name)
+                    .map(|(_, resource)| resource)
+                    .ok_or_else(|| BindingError::MissingResource { name: binding.name.clone() })?;
+                entries.push(wgpu::BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility: binding.visibility,
+                    ty: match check_resource(binding, resource)? {
+                        wgpu::BindingType::Sampler(wgpu_upstream::SamplerBindingType::Filtering)
+                            if self.samples_unfilterable(&binding.name, resources) =>
+                        {
+                            wgpu::BindingType::Sampler(wgpu_upstream::SamplerBindingType::NonFiltering)
+                        }
+                        ty => ty,
+                    },
+                    count: None,
+                });
+            }
+            groups.push(entries);
+        }
+        Ok(groups)
+    }
+
+    /// Whether the shader samples a texture that can't be filtered with `sampler`.
+    fn samples_unfilterable(&self, sampler: &str, resources: &[(&str, Resource)]) -> bool {
+        self.samples
+            .iter()
+            .filter(|(name, _)| name == sampler)
+            .filter_map(|(_, texture)| resources.iter().find(|(name, _)| name == texture))
+            .any(|(_, resource)| {
+                matches!(
+                    resource,
+                    Resource::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        ..
+                    }
+                )
+            })
+    }
+
+    /// Like `bind`, for shaders that follow a convention on the order of their bindings instead of
+    /// their names: the resources are given in the order of group and binding.
+    pub fn bind_in_order(&self, device: &wgpu::Device, resources: &[BindResource]) -> Result<Bindings, BindingError> {
+        if resources.len() != self.bindings.len() {
+            return Err(BindingError::ResourceCount {
+                expected: self.bindings.len(),
+                found: resources.len(),
+            });
+        }
+        let named: Vec<_> = self
+            .bindings
+            .iter()
+            .map(|binding| binding.name.as_str())
+            .zip(resources.iter().copied())
+            .collect();
+        self.bind(device, &named)
+    }
+}
+
+/// Bind group layouts and bind groups created by `ShaderReflection::bind`, group `i` is at index
+/// `i`.
+pub struct Bindings {
+    layouts: Vec<wgpu::BindGroupLayout>,
+    groups: Vec<wgpu::BindGroup>,
+}
+
+impl Bindings {
+    pub fn layouts(&self) -> Vec<&wgpu::BindGroupLayout> {
+        self.layouts.iter().collect()
+    }
+
+    pub fn groups(&self) -> &[wgpu::BindGroup] {
+        &self.groups
+    }
+
+    pub fn pipeline_layout(&self, device: &wgpu::Device, label: &str) -> wgpu::PipelineLayout {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &self.layouts(),
+            push_constant_ranges: &[],
+        })
+    }
+}
+
+/// The layout entry type for a resource bound to `binding`, or why it can't be bound.
+fn check_resource(binding: &ReflectedBinding, resource: &Resource) -> Result<wgpu::BindingType, BindingError> {
+    let mismatch = |found: String| BindingError::TypeMismatch {
+        name: binding.name.clone(),
+        expected: describe(&binding.ty),
+        found,
+    };
+    match (binding.ty, *resource) {
+        (
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                min_binding_size: Some(expected),
+                ..
+            },
+            Resource::Buffer { size },
+        ) if size != expected.get() => Err(BindingError::BufferSize {
+            name: binding.name.clone(),
+            expected: expected.get(),
+            found: size,
+        }),
+        (wgpu::BindingType::Buffer { .. }, Resource::Buffer { .. }) => Ok(binding.ty),
+        (wgpu::BindingType::Sampler(_), Resource::Sampler) => Ok(binding.ty),
+        (
+            wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled,
+            },
+            Resource::Texture {
+                sample_type: view_sample_type,
+                dimension,
+                format,
+            },
+        ) => {
+            let compatible = match (sample_type, view_sample_type) {
+                (wgpu::TextureSampleType::Float { .. }, wgpu::TextureSampleType::Float { .. }) => true,
+                (expected, found) => expected == found,
+            };
+            if !compatible || dimension != view_dimension {
+                return Err(mismatch(format!("a {:?} texture view of {:?}", dimension, format)));
+            }
+            Ok(wgpu::BindingType::Texture {
+                sample_type: view_sample_type,
+                view_dimension,
+                multisampled,
+            })
+        }
+        (
+            wgpu::BindingType::StorageTexture {
+                format: expected_format,
+                view_dimension,
+                ..
+            },
+            Resource::Texture { dimension, format, .. },
+        ) => {
+            if format != expected_format || dimension != view_dimension {
+                return Err(mismatch(format!("a {:?} texture view of {:?}", dimension, format)));
+            }
+            Ok(binding.ty)
+        }
+        (_, resource) => Err(mismatch(resource.kind().to_string())),
+    }
+}
+
+fn describe(ty: &wgpu::BindingType) -> String {
+    match ty {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            ..
+        } => "a uniform buffer".to_string(),
+        wgpu::BindingType::Buffer { .. } => "a storage buffer".to_string(),
+        wgpu::BindingType::Sampler(_) => "a sampler".to_string(),
+        wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension,
+            ..
+        } => format!("a {:?} texture of {:?}", view_dimension, sample_type),
+        wgpu::BindingType::StorageTexture {
+            format, view_dimension, ..
+        } => format!("a {:?} storage texture of {:?}", view_dimension, format),
+    }
+}
+
+/// `size` is the size of the type in bytes, uniform buffers need exactly that much.
+fn binding_type(ty: &naga::TypeInner, space: naga::AddressSpace, size: u32) -> Option<wgpu::BindingType> {
+    let binding_type = match space {
+        naga::AddressSpace::Uniform => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size as u64),
+        },
+        naga::AddressSpace::Storage { access } => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        naga::AddressSpace::Handle => match *ty {
+            naga::TypeInner::Sampler { comparison: false } => {
+                wgpu::BindingType::Sampler(wgpu_upstream::SamplerBindingType::Filtering)
+            }
+            naga::TypeInner::Sampler { comparison: true } => {
+                wgpu::BindingType::Sampler(wgpu_upstream::SamplerBindingType::Comparison)
+            }
+            naga::TypeInner::Image { dim, arrayed, class } => {
+                let view_dimension = view_dimension(dim, arrayed);
+                match class {
+                    naga::ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            naga::ScalarKind::Float => wgpu::TextureSampleType::Float { filterable: true },
+                            naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            naga::ScalarKind::Bool => return None,
+                        },
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    naga::ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+                        access: if access == naga::StorageAccess::STORE {
+                            wgpu::StorageTextureAccess::WriteOnly
+                        } else if access == naga::StorageAccess::LOAD {
+                            wgpu::StorageTextureAccess::ReadOnly
+                        } else {
+                            wgpu::StorageTextureAccess::ReadWrite
+                        },
+                        format: texture_format(format),
+                        view_dimension,
+                    },
+                }
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(binding_type)
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+fn texture_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgpu::TextureFormat as Tf;
+
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Float => Tf::Rg11b10Float,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPUTE: &str = "
+struct Uniforms {
+    scale: f32,
+};
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var inTexture: texture_2d<f32>;
+@group(0) @binding(2) var inSampler: sampler;
+@group(2) @binding(0) var outTexture: texture_storage_2d<rgba32float, write>;
+@compute @workgroup_size(1) fn main() {
+    let color = textureSampleLevel(inTexture, inSampler, vec2(0.5), 0.0);
+    textureStore(outTexture, vec2(0), color * uniforms.scale);
+}
+";
+
+    fn texture(format: wgpu::TextureFormat) -> Resource {
+        Resource::Texture {
+            sample_type: format.sample_type(None).unwrap(),
+            dimension: wgpu::TextureViewDimension::D2,
+            format,
+        }
+    }
+
+    /// Resources that can be bound to `COMPUTE`, with `input` for `inTexture`.
+    fn resources(input: wgpu::TextureFormat) -> Vec<(&'static str, Resource)> {
+        vec![
+            ("uniforms", Resource::Buffer { size: 4 }),
+            ("inTexture", texture(input)),
+            ("inSampler", Resource::Sampler),
+            ("outTexture", texture(wgpu::TextureFormat::Rgba32Float)),
+        ]
+    }
+
+    fn replace(resources: &mut [(&str, Resource)], name: &str, resource: Resource) {
+        resources.iter_mut().find(|(bound, _)| *bound == name).unwrap().1 = resource;
+    }
+
+    fn entry_type(groups: &[Vec<wgpu::BindGroupLayoutEntry>], group: usize, binding: u32) -> wgpu::BindingType {
+        groups[group].iter().find(|entry| entry.binding == binding).unwrap().ty
+    }
+
+    #[test]
+    fn reflects_the_size_of_uniforms() {
+        let reflection = ShaderReflection::from_wgsl(
+            "
+struct Uniforms {
+    color: vec3<f32>,
+    seed: u32,
+    scale: f32,
+};
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(1) @binding(2) var outTexture: texture_storage_2d<rgba8unorm, write>;
+@compute @workgroup_size(1) fn main() { textureStore(outTexture, vec2(0), vec4(uniforms.color, uniforms.scale)); }
+",
+        )
+        .unwrap();
+        assert_eq!(reflection.group_count(), 2);
+        let uniforms = reflection.binding("uniforms").unwrap();
+        assert_eq!(
+            uniforms.ty,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(32),
+            }
+        );
+        let output = reflection.binding("outTexture").unwrap();
+        assert_eq!((output.group, output.binding), (1, 2));
+    }
+
+    #[test]
+    fn records_the_textures_each_sampler_samples() {
+        let reflection = ShaderReflection::from_wgsl(
+            "
+@group(0) @binding(0) var color: texture_2d<f32>;
+@group(0) @binding(1) var depth: texture_2d<f32>;
+@group(0) @binding(2) var linear: sampler;
+@group(0) @binding(3) var nearest: sampler;
+fn sample_depth(uv: vec2<f32>) -> vec4<f32> { return textureSampleLevel(depth, nearest, uv, 0.0); }
+@fragment fn main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(color, linear, uv) + sample_depth(uv) + textureSample(color, linear, uv * 2.0);
+}
+",
+        )
+        .unwrap();
+        let samples = [
+            ("nearest".to_string(), "depth".to_string()),
+            ("linear".to_string(), "color".to_string()),
+        ];
+        assert_eq!(reflection.samples, samples);
+    }
+
+    #[test]
+    fn counts_the_empty_groups_below_the_last() {
+        let reflection = ShaderReflection::from_wgsl(COMPUTE).unwrap();
+        assert_eq!(reflection.group_count(), 3);
+        let groups = reflection.layout_entries(&resources(wgpu::TextureFormat::Rgba8Unorm)).unwrap();
+        let bindings: Vec<Vec<u32>> = groups
+            .iter()
+            .map(|entries| entries.iter().map(|entry| entry.binding).collect())
+            .collect();
+        assert_eq!(bindings, [vec![0, 1, 2], vec![], vec![0]]);
+        assert_eq!(ShaderReflection::default().group_count(), 0);
+    }
+
+    #[test]
+    fn rejects_resources_for_unknown_bindings() {
+        let reflection = ShaderReflection::from_wgsl(COMPUTE).unwrap();
+        let mut resources = resources(wgpu::TextureFormat::Rgba8Unorm);
+        resources.push(("outputTexture", Resource::Sampler));
+        let error = reflection.layout_entries(&resources).unwrap_err();
+        assert_eq!(error, BindingError::UnknownBinding { name: "outputTexture".to_string() });
+    }
+
+    #[test]
+    fn rejects_missing_resources() {
+        let reflection = ShaderReflection::from_wgsl(COMPUTE).unwrap();
+        let mut resources = resources(wgpu::TextureFormat::Rgba8Unorm);
+        resources.retain(|(name, _)| *name != "inSampler");
+        let error = reflection.layout_entries(&resources).unwrap_err();
+        assert_eq!(error, BindingError::MissingResource { name: "inSampler".to_string() });
+    }
+
+    #[test]
+    fn rejects_resources_of_the_wrong_type() {
+        let reflection = ShaderReflection::from_wgsl(COMPUTE).unwrap();
+        let bind = |name: &str, resource: Resource| {
+            let mut resources = resources(wgpu::TextureFormat::Rgba8Unorm);
+            replace(&mut resources, name, resource);
+            reflection.layout_entries(&resources).unwrap_err()
+        };
+
+        assert_eq!(
+            bind("uniforms", Resource::Sampler),
+            BindingError::TypeMismatch {
+                name: "uniforms".to_string(),
+                expected: "a uniform buffer".to_string(),
+                found: "a sampler".to_string(),
+            }
+        );
+        assert!(matches!(
+            bind("inSampler", texture(wgpu::TextureFormat::Rgba8Unorm)),
+            BindingError::TypeMismatch { name, .. } if name == "inSampler"
+        ));
+        assert!(matches!(
+            bind("inTexture", texture(wgpu::TextureFormat::R32Uint)),
+            BindingError::TypeMismatch { name, .. } if name == "inTexture"
+        ));
+        let cube = Resource::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            dimension: wgpu::TextureViewDimension::Cube,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+        };
+        assert!(matches!(bind("inTexture", cube), BindingError::TypeMismatch { name, .. } if name == "inTexture"));
+        assert_eq!(
+            bind("outTexture", texture(wgpu::TextureFormat::Rgba8Unorm)),
+            BindingError::TypeMismatch {
+                name: "outTexture".to_string(),
+                expected: "a D2 storage texture of Rgba32Float".to_string(),
+                found: "a D2 texture view of Rgba8Unorm".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_uniform_buffers_of_another_size() {
+        let reflection = ShaderReflection::from_wgsl(COMPUTE).unwrap();
+        let mut resources = resources(wgpu::TextureFormat::Rgba8Unorm);
+        replace(&mut resources, "uniforms", Resource::Buffer { size: 8 });
+        let error = reflection.layout_entries(&resources).unwrap_err();
+        assert_eq!(
+            error,
+            BindingError::BufferSize {
+                name: "uniforms".to_string(),
+                expected: 4,
+                found: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn samples_unfilterable_textures_without_filtering() {
+        let reflection = ShaderReflection::from_wgsl(COMPUTE).unwrap();
+
+        let groups = reflection.layout_entries(&resources(wgpu::TextureFormat::Rgba8Unorm)).unwrap();
+        let filtering = wgpu::BindingType::Sampler(wgpu_upstream::SamplerBindingType::Filtering);
+        assert_eq!(entry_type(&groups, 0, 2), filtering);
+
+        let groups = reflection.layout_entries(&resources(wgpu::TextureFormat::Rgba32Float)).unwrap();
+        assert_eq!(
+            entry_type(&groups, 0, 1),
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            }
+        );
+        let non_filtering = wgpu::BindingType::Sampler(wgpu_upstream::SamplerBindingType::NonFiltering);
+        assert_eq!(entry_type(&groups, 0, 2), non_filtering);
+    }
+
+    #[test]
+    fn merges_the_visibility_of_shared_bindings() {
+        let vertex = ShaderReflection::from_wgsl(
+            "
+@group(0) @binding(0) var<uniform> transform: mat4x4<f32>;
+@vertex fn main(@location(0) position: vec4<f32>) -> @builtin(position) vec4<f32> { return transform * position; }
+",
+        )
+        .unwrap();
+        let fragment = ShaderReflection::from_wgsl(
+            "
+@group(0) @binding(0) var<uniform> transform: mat4x4<f32>;
+@group(0) @binding(1) var<uniform> tint: vec4<f32>;
+@fragment fn main() -> @location(0) vec4<f32> { return transform[0] * tint; }
+",
+        )
+        .unwrap();
+        let merged = vertex.merge(fragment);
+
+        let visibility: Vec<_> = merged
+            .bindings()
+            .iter()
+            .map(|binding| (binding.name.as_str(), binding.visibility))
+            .collect();
+        assert_eq!(
+            visibility,
+            [
+                ("transform", wgpu::ShaderStages::VERTEX_FRAGMENT),
+                ("tint", wgpu::ShaderStages::FRAGMENT),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_storage_formats() {
+        assert_eq!(texture_format(naga::StorageFormat::Rgba8Unorm), wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(texture_format(naga::StorageFormat::Rgba32Float), wgpu::TextureFormat::Rgba32Float);
+        assert_eq!(texture_format(naga::StorageFormat::R32Uint), wgpu::TextureFormat::R32Uint);
+        assert_eq!(texture_format(naga::StorageFormat::Rg11b10Float), wgpu::TextureFormat::Rg11b10Float);
+        assert_eq!(texture_format(naga::StorageFormat::Rgba16Snorm), wgpu::TextureFormat::Rgba16Snorm);
+
+        for (wgsl, format) in [
+            ("rgba8unorm", wgpu::TextureFormat::Rgba8Unorm),
+            ("rgba16float", wgpu::TextureFormat::Rgba16Float),
+            ("r32float", wgpu::TextureFormat::R32Float),
+            ("rg32sint", wgpu::TextureFormat::Rg32Sint),
+        ] {
+            let source = format!(
+                "@group(0) @binding(0) var output: texture_storage_2d<{}, write>;\n\
+                 @compute @workgroup_size(1) fn main() {{}}",
+                wgsl
+            );
+            let reflection = ShaderReflection::from_wgsl(&source).unwrap();
+            assert_eq!(
+                reflection.binding("output").unwrap().ty,
+                wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                "{}",
+                wgsl
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use nannou::wgpu;
+
+use crate::preprocessor::Preprocessor;
+use crate::reflection::{BindResource, Bindings, ShaderReflection};
+use crate::uniforms::{UniformBuffer, WgslUniform};
+
+/// A fragment shader drawn over the whole output texture, the render counterpart of a
+/// `ComputeKernel`.
+///
+/// The WGSL module only contains the fragment stage, the vertex stage is provided. It is
+/// preprocessed like the one of a `ComputeKernel`, so it can include the struct of its uniforms:
+///
+/// ```wgsl
+/// #include "uniforms/Uniforms.wgsl"
+///
+/// @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+/// @group(0) @binding(1) var inTexture: texture_2d<f32>;
+/// @group(0) @binding(2) var inSampler: sampler;
+///
+/// @fragment
+/// fn main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+///     return textureSample(inTexture, inSampler, tex_coords);
+/// }
+/// ```
+///
+/// With several inputs they take `@binding(1)` up to `@binding(n)` and the linear sampler
+/// `@binding(n + 1)`. The variable names don't matter, the layout is reflected from the shader.
+pub struct FragmentPass<U: WgslUniform> {
+    uniforms: UniformBuffer<U>,
+    bindings: Bindings,
+    pipeline: wgpu::RenderPipeline,
+    output_view: wgpu::TextureView,
+}
+
+impl<U: WgslUniform> FragmentPass<U> {
+    /// `output` needs `wgpu::TextureUsages::RENDER_ATTACHMENT`.
+    pub fn new(
+        device: &wgpu::Device,
+        wgsl: &str,
+        inputs: &[&wgpu::Texture],
+        output: &wgpu::Texture,
+        uniforms: U,
+    ) -> Self {
+        let fs = Preprocessor::new()
+            .with_uniform::<U>()
+            .process("fragment-pass.wgsl", wgsl)
+            .unwrap_or_else(|err| panic!("fragment pass: {}", err));
+        let vs_mod = device.create_shader_module(wgpu::include_wgsl!("shaders/fullscreen.wgsl"));
+        let fs_mod = device.create_shader_module(fs.descriptor("fragment-pass"));
+
+        let uniforms = UniformBuffer::new(device, "fragment-pass-uniforms", uniforms);
+
+        let sampler = device.create_sampler(&wgpu::SamplerBuilder::new().into_descriptor());
+
+        let input_views: Vec<_> = inputs.iter().map(|input| input.view().build()).collect();
+        let mut resources = vec![BindResource::Buffer(uniforms.buffer())];
+        resources.extend(input_views.iter().map(BindResource::TextureView));
+        resources.push(BindResource::Sampler(&sampler));
+
+        let bindings = ShaderReflection::bind_or_panic(&[&fs.source], "fragment pass", |reflection| {
+            reflection.bind_in_order(device, &resources)
+        });
+        let pipeline_layout = bindings.pipeline_layout(device, "fragment-pass");
+        let pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vs_mod)
+            .fragment_shader(&fs_mod)
+            .color_format(output.format())
+            .primitive_topology(wgpu::PrimitiveTopology::TriangleList)
+            .build(device);
+
+        FragmentPass {
+            uniforms,
+            bindings,
+            pipeline,
+            output_view: output.view().build(),
+        }
+    }
+
+    /// Uploads new uniform values if they changed, they will be visible to the next submitted
+    /// draw.
+    pub fn write_uniforms(&mut self, queue: &wgpu::Queue, uniforms: U) {
+        self.uniforms.set(uniforms);
+        self.uniforms.flush(queue);
+    }
+
+    pub fn uniforms(&self) -> &UniformBuffer<U> {
+        &self.uniforms
+    }
+
+    pub fn uniforms_mut(&mut self) -> &mut UniformBuffer<U> {
+        &mut self.uniforms
+    }
+
+    /// Records a render pass that overwrites the whole output texture.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass = wgpu::RenderPassBuilder::new()
+            .color_attachment(&self.output_view, |color| color)
+            .begin(encoder);
+        render_pass.set_pipeline(&self.pipeline);
+        for (i, bind_group) in self.bindings.groups().iter().enumerate() {
+            render_pass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        render_pass.draw(0..3, 0..1);
+    }
+}
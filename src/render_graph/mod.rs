@@ -0,0 +1,543 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use nannou::wgpu;
+
+use crate::compute_kernel::{ComputeKernel, create_storage_texture_with_format, WorkgroupSize};
+use crate::render_graph::fragment_pass::FragmentPass;
+use crate::uniforms::{UniformBuffer, WgslUniform};
+
+pub mod fragment_pass;
+
+/// Format of the textures allocated by the graph, unless set with `texture_format`.
+pub const TRANSIENT_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderGraphError {
+    /// Two passes were given the same name.
+    DuplicatePass { pass: String },
+    /// A pass reads a texture that is neither imported nor written by another pass.
+    MissingTexture { pass: String, texture: String },
+    /// Every texture can only be written by one pass.
+    MultipleWriters { texture: String, passes: [String; 2] },
+    /// The passes depend on each other, e.g. a pass reads its own output.
+    Cycle { passes: Vec<String> },
+}
+
+impl fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderGraphError::DuplicatePass { pass } => write!(f, "there is more than one pass named `{}`", pass),
+            RenderGraphError::MissingTexture { pass, texture } => write!(
+                f,
+                "pass `{}` reads `{}`, which is neither imported nor written by any pass",
+                pass, texture
+            ),
+            RenderGraphError::MultipleWriters { texture, passes: [first, second] } => write!(
+                f,
+                "`{}` is written by both `{}` and `{}`",
+                texture, first, second
+            ),
+            RenderGraphError::Cycle { passes } => write!(
+                f,
+                "passes {} depend on each other",
+                passes.iter().map(|pass| format!("`{}`", pass)).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {}
+
+/// Refers to a pass of a built `RenderGraph`, to update its uniforms.
+pub struct PassHandle<U> {
+    index: usize,
+    _uniforms: PhantomData<fn() -> U>,
+}
+
+impl<U> Clone for PassHandle<U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U> Copy for PassHandle<U> {}
+
+/// What the graph needs from a pass once it is built, independently of its uniforms type.
+trait Pass {
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder);
+    fn uniform_buffer(&self) -> &dyn Any;
+    fn uniform_buffer_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<U: WgslUniform> Pass for ComputeKernel<U> {
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(encoder);
+    }
+
+    fn uniform_buffer(&self) -> &dyn Any {
+        self.uniforms()
+    }
+
+    fn uniform_buffer_mut(&mut self) -> &mut dyn Any {
+        self.uniforms_mut()
+    }
+}
+
+impl<U: WgslUniform> Pass for FragmentPass<U> {
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        FragmentPass::encode(self, encoder);
+    }
+
+    fn uniform_buffer(&self) -> &dyn Any {
+        self.uniforms()
+    }
+
+    fn uniform_buffer_mut(&mut self) -> &mut dyn Any {
+        self.uniforms_mut()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PassKind {
+    Compute,
+    Fragment,
+}
+
+type BuildPass<'a> = Box<dyn FnOnce(&wgpu::Device, &[&wgpu::Texture], &wgpu::Texture) -> Box<dyn Pass> + 'a>;
+
+struct PassDesc<'a> {
+    name: String,
+    kind: PassKind,
+    inputs: Vec<String>,
+    output: String,
+    build: BuildPass<'a>,
+}
+
+/// Describes passes by the names of the textures they read and write, the order in which they
+/// run and the textures between them are worked out by `build`.
+///
+/// ```ignore
+/// let mut builder = RenderGraphBuilder::new(texture.size());
+/// builder.import("source", &texture);
+/// builder.import("output", &storage_texture);
+/// builder.compute_pass("edges", include_str!("shaders/dog.wgsl"), &["blurred"], "edges", dog_uniforms);
+/// builder.compute_pass("blur", include_str!("shaders/blur.wgsl"), &["source"], "blurred", blur_uniforms);
+/// builder.fragment_pass("composite", include_str!("shaders/composite.wgsl"), &["source", "edges"], "output", uniforms);
+/// let graph = builder.build(device)?;
+/// ```
+pub struct RenderGraphBuilder<'a> {
+    size: [u32; 2],
+    imports: HashMap<String, &'a wgpu::Texture>,
+    formats: HashMap<String, wgpu::TextureFormat>,
+    passes: Vec<PassDesc<'a>>,
+}
+
+impl<'a> RenderGraphBuilder<'a> {
+    /// `size` is the size of every texture allocated by the graph.
+    pub fn new(size: [u32; 2]) -> Self {
+        RenderGraphBuilder {
+            size,
+            imports: HashMap::new(),
+            formats: HashMap::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    /// Makes a texture created outside of the graph available to its passes, either as an input
+    /// (e.g. the source image) or as an output (e.g. the texture drawn to the window).
+    pub fn import(&mut self, name: &str, texture: &'a wgpu::Texture) -> &mut Self {
+        self.imports.insert(name.to_string(), texture);
+        self
+    }
+
+    /// Overrides `TRANSIENT_TEXTURE_FORMAT` for a texture allocated by the graph.
+    pub fn texture_format(&mut self, name: &str, format: wgpu::TextureFormat) -> &mut Self {
+        self.formats.insert(name.to_string(), format);
+        self
+    }
+
+    /// Adds a `ComputeKernel`, `inputs` are bound in order after the uniforms.
+    pub fn compute_pass<U: WgslUniform>(&mut self, name: &str, wgsl: &str, inputs: &[&str], output: &str, uniforms: U) -> PassHandle<U> {
+        let wgsl = wgsl.to_string();
+        self.add_pass(name, PassKind::Compute, inputs, output, Box::new(move |device, inputs, output| {
+            Box::new(ComputeKernel::with_inputs(device, &wgsl, WorkgroupSize::default(), inputs, output, uniforms))
+        }))
+    }
+
+    /// Adds a `FragmentPass`, `inputs` are bound in order after the uniforms.
+    pub fn fragment_pass<U: WgslUniform>(&mut self, name: &str, wgsl: &str, inputs: &[&str], output: &str, uniforms: U) -> PassHandle<U> {
+        let wgsl = wgsl.to_string();
+        self.add_pass(name, PassKind::Fragment, inputs, output, Box::new(move |device, inputs, output| {
+            Box::new(FragmentPass::new(device, &wgsl, inputs, output, uniforms))
+        }))
+    }
+
+    fn add_pass<U>(&mut self, name: &str, kind: PassKind, inputs: &[&str], output: &str, build: BuildPass<'a>) -> PassHandle<U> {
+        self.passes.push(PassDesc {
+            name: name.to_string(),
+            kind,
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            output: output.to_string(),
+            build,
+        });
+        PassHandle {
+            index: self.passes.len() - 1,
+            _uniforms: PhantomData,
+        }
+    }
+
+    /// Sorts the passes, allocates the textures that are not imported and creates every pipeline.
+    ///
+    /// A texture allocated by the graph is reused by later passes once every pass reading it ran.
+    pub fn build(self, device: &wgpu::Device) -> Result<RenderGraph, RenderGraphError> {
+        let order = self.execution_order()?;
+        let allocation = self.allocate_textures(&order);
+
+        let RenderGraphBuilder { size, imports, passes, .. } = self;
+        let textures: Vec<wgpu::Texture> = allocation
+            .textures
+            .iter()
+            .map(|&(format, kind)| create_transient_texture(device, size, format, kind))
+            .collect();
+        let slots = allocation.slots;
+        let mut passes: Vec<Option<PassDesc>> = passes.into_iter().map(Some).collect();
+        let mut built: Vec<Option<Box<dyn Pass>>> = passes.iter().map(|_| None).collect();
+        let mut names = Vec::with_capacity(passes.len());
+
+        for &index in &order {
+            let pass = passes[index].take().expect("every pass appears once in the execution order");
+            let texture = |name: &String| match imports.get(name) {
+                Some(texture) => *texture,
+                None => &textures[slots[name]],
+            };
+            let inputs: Vec<&wgpu::Texture> = pass.inputs.iter().map(texture).collect();
+            built[index] = Some((pass.build)(device, &inputs, texture(&pass.output)));
+            names.push((index, pass.name));
+        }
+
+        names.sort_by_key(|(index, _)| *index);
+        Ok(RenderGraph {
+            passes: built.into_iter().map(|pass| pass.expect("every pass is built")).collect(),
+            names: names.into_iter().map(|(_, name)| name).collect(),
+            order,
+            textures,
+            slots,
+        })
+    }
+
+    /// Assigns every texture that is not imported to a slot, running the passes in `order`.
+    ///
+    /// A slot is freed after the last pass using its texture and reused by the next output of the
+    /// same format and kind of pass.
+    fn allocate_textures(&self, order: &[usize]) -> TextureAllocation {
+        // Position in the execution order of the last pass using each allocated texture
+        let mut last_use = HashMap::new();
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            for texture in pass.inputs.iter().chain(Some(&pass.output)) {
+                if !self.imports.contains_key(texture) {
+                    last_use.insert(texture.clone(), position);
+                }
+            }
+        }
+
+        let mut allocation = TextureAllocation {
+            textures: Vec::new(),
+            slots: HashMap::new(),
+        };
+        let mut free: Vec<usize> = Vec::new();
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+
+            if !self.imports.contains_key(&pass.output) {
+                let format = self.formats.get(&pass.output).copied().unwrap_or(TRANSIENT_TEXTURE_FORMAT);
+                let reusable = free.iter().position(|&slot| allocation.textures[slot] == (format, pass.kind));
+                let slot = match reusable {
+                    Some(i) => free.swap_remove(i),
+                    None => {
+                        allocation.textures.push((format, pass.kind));
+                        allocation.textures.len() - 1
+                    }
+                };
+                allocation.slots.insert(pass.output.clone(), slot);
+            }
+
+            // Inputs are released after the output is allocated, so a pass never reads and
+            // writes the same texture
+            for name in pass.inputs.iter().chain(Some(&pass.output)) {
+                if last_use.get(name) == Some(&position) && !free.contains(&allocation.slots[name]) {
+                    free.push(allocation.slots[name]);
+                }
+            }
+        }
+        allocation
+    }
+
+    /// Indices of the passes, sorted so every pass runs after the ones writing its inputs.
+    ///
+    /// Passes that don't depend on each other keep the order they were added in.
+    fn execution_order(&self) -> Result<Vec<usize>, RenderGraphError> {
+        let mut writers: HashMap<&str, usize> = HashMap::new();
+        for (index, pass) in self.passes.iter().enumerate() {
+            if self.passes[..index].iter().any(|other| other.name == pass.name) {
+                return Err(RenderGraphError::DuplicatePass { pass: pass.name.clone() });
+            }
+            if let Some(&writer) = writers.get(pass.output.as_str()) {
+                return Err(RenderGraphError::MultipleWriters {
+                    texture: pass.output.clone(),
+                    passes: [self.passes[writer].name.clone(), pass.name.clone()],
+                });
+            }
+            writers.insert(&pass.output, index);
+        }
+
+        let mut dependencies = Vec::with_capacity(self.passes.len());
+        for pass in &self.passes {
+            let mut pass_dependencies = Vec::new();
+            for input in &pass.inputs {
+                match writers.get(input.as_str()) {
+                    Some(&writer) => pass_dependencies.push(writer),
+                    None if self.imports.contains_key(input) => {}
+                    None => {
+                        return Err(RenderGraphError::MissingTexture {
+                            pass: pass.name.clone(),
+                            texture: input.clone(),
+                        })
+                    }
+                }
+            }
+            dependencies.push(pass_dependencies);
+        }
+
+        let mut done = vec![false; self.passes.len()];
+        let mut order = Vec::with_capacity(self.passes.len());
+        while order.len() < self.passes.len() {
+            let ready = (0..self.passes.len())
+                .find(|&index| !done[index] && dependencies[index].iter().all(|&dependency| done[dependency]));
+            match ready {
+                Some(index) => {
+                    done[index] = true;
+                    order.push(index);
+                }
+                None => {
+                    let passes = (0..self.passes.len())
+                        .filter(|&index| !done[index])
+                        .map(|index| self.passes[index].name.clone())
+                        .collect();
+                    return Err(RenderGraphError::Cycle { passes });
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+/// Where `allocate_textures` puts the textures of a graph.
+struct TextureAllocation {
+    /// Format of each slot and the kind of pass writing to it, which sets its usages.
+    textures: Vec<(wgpu::TextureFormat, PassKind)>,
+    /// Slot of each texture that is not imported.
+    slots: HashMap<String, usize>,
+}
+
+fn create_transient_texture(device: &wgpu::Device, size: [u32; 2], format: wgpu::TextureFormat, kind: PassKind) -> wgpu::Texture {
+    match kind {
+        PassKind::Compute => create_storage_texture_with_format(device, size, format),
+        PassKind::Fragment => wgpu::TextureBuilder::new()
+            .size(size)
+            .format(format)
+            .usage(
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            )
+            .build(device),
+    }
+}
+
+/// Passes chained through named textures, see `RenderGraphBuilder`.
+pub struct RenderGraph {
+    passes: Vec<Box<dyn Pass>>,
+    names: Vec<String>,
+    order: Vec<usize>,
+    textures: Vec<wgpu::Texture>,
+    slots: HashMap<String, usize>,
+}
+
+impl RenderGraph {
+    /// Uploads new uniform values for a pass if they changed, they will be visible to the next
+    /// submitted `encode`.
+    pub fn write_uniforms<U: WgslUniform>(&mut self, queue: &wgpu::Queue, pass: PassHandle<U>, uniforms: U) {
+        let buffer = self.uniforms_mut(pass);
+        buffer.set(uniforms);
+        buffer.flush(queue);
+    }
+
+    /// Panics if the handle comes from another graph.
+    pub fn uniforms<U: WgslUniform>(&self, pass: PassHandle<U>) -> &UniformBuffer<U> {
+        self.passes[pass.index]
+            .uniform_buffer()
+            .downcast_ref()
+            .expect("the pass handle belongs to another render graph")
+    }
+
+    /// Panics if the handle comes from another graph.
+    pub fn uniforms_mut<U: WgslUniform>(&mut self, pass: PassHandle<U>) -> &mut UniformBuffer<U> {
+        self.passes[pass.index]
+            .uniform_buffer_mut()
+            .downcast_mut()
+            .expect("the pass handle belongs to another render graph")
+    }
+
+    /// Records every pass, in execution order.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        for &index in &self.order {
+            self.passes[index].encode(encoder);
+        }
+    }
+
+    /// Names of the passes in the order they run.
+    pub fn execution_order(&self) -> impl Iterator<Item = &str> {
+        self.order.iter().map(|&index| self.names[index].as_str())
+    }
+
+    /// A texture allocated by the graph, for debugging.
+    ///
+    /// Textures are shared between passes that don't run at the same time, so after `encode` it
+    /// may hold the output of a later pass.
+    pub fn texture(&self, name: &str) -> Option<&wgpu::Texture> {
+        self.slots.get(name).map(|&slot| &self.textures[slot])
+    }
+
+    /// Amount of textures allocated by the graph, imported ones excluded.
+    pub fn allocated_texture_count(&self) -> usize {
+        self.textures.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adds a pass that is never built, the ordering and allocation don't need a GPU.
+    fn add(builder: &mut RenderGraphBuilder, name: &str, inputs: &[&str], output: &str) {
+        builder.add_pass::<()>(name, PassKind::Compute, inputs, output, Box::new(|_, _, _| unreachable!()));
+    }
+
+    fn order_names(builder: &RenderGraphBuilder) -> Vec<String> {
+        let order = builder.execution_order().unwrap();
+        order.into_iter().map(|index| builder.passes[index].name.clone()).collect()
+    }
+
+    #[test]
+    fn runs_passes_after_the_ones_writing_their_inputs() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "composite", &["source", "edges"], "output");
+        add(&mut builder, "edges", &["blurred"], "edges");
+        add(&mut builder, "source", &[], "source");
+        add(&mut builder, "blur", &["source"], "blurred");
+        assert_eq!(order_names(&builder), ["source", "blur", "edges", "composite"]);
+    }
+
+    #[test]
+    fn keeps_independent_passes_in_the_order_they_were_added() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "b", &[], "b");
+        add(&mut builder, "a", &[], "a");
+        add(&mut builder, "c", &[], "c");
+        assert_eq!(order_names(&builder), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "start", &[], "start");
+        add(&mut builder, "a", &["start", "b"], "a");
+        add(&mut builder, "b", &["a"], "b");
+        let error = builder.execution_order().unwrap_err();
+        assert_eq!(error, RenderGraphError::Cycle { passes: vec!["a".to_string(), "b".to_string()] });
+    }
+
+    #[test]
+    fn reports_passes_reading_their_own_output() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "feedback", &["feedback"], "feedback");
+        let error = builder.execution_order().unwrap_err();
+        assert_eq!(error, RenderGraphError::Cycle { passes: vec!["feedback".to_string()] });
+    }
+
+    #[test]
+    fn reports_missing_textures() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "blur", &[], "blurred");
+        add(&mut builder, "edges", &["blurred", "depth"], "edges");
+        let error = builder.execution_order().unwrap_err();
+        assert_eq!(
+            error,
+            RenderGraphError::MissingTexture {
+                pass: "edges".to_string(),
+                texture: "depth".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn reports_textures_written_twice() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "a", &[], "out");
+        add(&mut builder, "b", &[], "out");
+        let error = builder.execution_order().unwrap_err();
+        assert_eq!(
+            error,
+            RenderGraphError::MultipleWriters {
+                texture: "out".to_string(),
+                passes: ["a".to_string(), "b".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn reuses_textures_after_their_last_use() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "a", &[], "1");
+        add(&mut builder, "b", &["1"], "2");
+        add(&mut builder, "c", &["2"], "3");
+        add(&mut builder, "d", &["3"], "4");
+        let allocation = builder.allocate_textures(&builder.execution_order().unwrap());
+        assert_eq!(allocation.textures.len(), 2);
+        // A pass never writes the texture it reads
+        assert_ne!(allocation.slots["1"], allocation.slots["2"]);
+        assert_eq!(allocation.slots["1"], allocation.slots["3"]);
+        assert_eq!(allocation.slots["2"], allocation.slots["4"]);
+    }
+
+    #[test]
+    fn keeps_textures_read_later() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        add(&mut builder, "a", &[], "1");
+        add(&mut builder, "b", &["1"], "2");
+        add(&mut builder, "c", &["2"], "3");
+        add(&mut builder, "d", &["1", "3"], "4");
+        let allocation = builder.allocate_textures(&builder.execution_order().unwrap());
+        assert_eq!(allocation.textures.len(), 3);
+        assert_ne!(allocation.slots["3"], allocation.slots["1"]);
+        assert_ne!(allocation.slots["4"], allocation.slots["1"]);
+        assert_ne!(allocation.slots["4"], allocation.slots["3"]);
+    }
+
+    #[test]
+    fn only_reuses_textures_of_the_same_format() {
+        let mut builder = RenderGraphBuilder::new([1, 1]);
+        builder.texture_format("2", wgpu::TextureFormat::Rgba8Unorm);
+        add(&mut builder, "a", &[], "1");
+        add(&mut builder, "b", &["1"], "2");
+        add(&mut builder, "c", &["2"], "3");
+        let allocation = builder.allocate_textures(&builder.execution_order().unwrap());
+        assert_eq!(allocation.textures.len(), 2);
+        assert_eq!(allocation.slots["1"], allocation.slots["3"]);
+        assert_eq!(allocation.textures[allocation.slots["2"]].0, wgpu::TextureFormat::Rgba8Unorm);
+    }
+}
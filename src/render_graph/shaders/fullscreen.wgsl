@@ -0,0 +1,13 @@
+// A triangle covering the whole target, used as the vertex stage of every fragment pass.
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @builtin(position) out_pos: vec4<f32>,
+};
+
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // (0, 0), (2, 0) and (0, 2) in texture coordinates, the part outside [0, 1] is clipped
+    let tex_coords = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let out_pos = vec4<f32>(tex_coords.x * 2.0 - 1.0, 1.0 - tex_coords.y * 2.0, 0.0, 1.0);
+    return VertexOutput(tex_coords, out_pos);
+}
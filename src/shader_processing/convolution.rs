@@ -0,0 +1,171 @@
+use crate::shader_processing::model::ConvolutionUniform;
+
+/// Largest kernel side that fits in `ConvolutionUniform`.
+pub const MAX_KERNEL_SIZE: usize = 9;
+
+/// Weights are packed four per `vec4<f32>` because arrays in WGSL uniform buffers have a stride
+/// of 16 bytes.
+pub const PACKED_WEIGHTS_LEN: usize = (MAX_KERNEL_SIZE * MAX_KERNEL_SIZE).div_ceil(4);
+
+/// A centered, square convolution matrix of odd size, applied as `sum(weights * pixels) / divisor + bias`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvolutionKernel {
+    size: usize,
+    weights: Vec<f32>,
+    divisor: f32,
+    bias: f32,
+}
+
+impl ConvolutionKernel {
+    /// Creates a kernel from `size * size` row-major weights.
+    ///
+    /// The divisor defaults to the sum of the weights, or `1.0` when they add up to zero, so the
+    /// kernel preserves the brightness of the image.
+    ///
+    /// Panics if `size` is even, bigger than `MAX_KERNEL_SIZE` or doesn't match the weights.
+    pub fn new(size: usize, weights: Vec<f32>) -> Self {
+        assert!(size % 2 == 1, "convolution kernels must have an odd size, got {}", size);
+        assert!(size <= MAX_KERNEL_SIZE, "convolution kernels can be at most {}x{}", MAX_KERNEL_SIZE, MAX_KERNEL_SIZE);
+        assert_eq!(weights.len(), size * size, "a {}x{} kernel needs {} weights", size, size, size * size);
+
+        let sum: f32 = weights.iter().sum();
+        let divisor = if sum.abs() > f32::EPSILON { sum } else { 1.0 };
+        ConvolutionKernel {
+            size,
+            weights,
+            divisor,
+            bias: 0.0,
+        }
+    }
+
+    pub fn with_divisor(mut self, divisor: f32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    pub fn with_bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    pub fn identity() -> Self {
+        ConvolutionKernel::new(1, vec![1.0])
+    }
+
+    pub fn sharpen() -> Self {
+        ConvolutionKernel::new(3, vec![
+            0.0, -1.0, 0.0,
+            -1.0, 5.0, -1.0,
+            0.0, -1.0, 0.0,
+        ])
+    }
+
+    pub fn emboss() -> Self {
+        ConvolutionKernel::new(3, vec![
+            -2.0, -1.0, 0.0,
+            -1.0, 1.0, 1.0,
+            0.0, 1.0, 2.0,
+        ])
+    }
+
+    pub fn box_blur(size: usize) -> Self {
+        ConvolutionKernel::new(size, vec![1.0; size * size])
+    }
+
+    /// Laplacian edge detection, flat areas become black.
+    pub fn edge_detect() -> Self {
+        ConvolutionKernel::new(3, vec![
+            -1.0, -1.0, -1.0,
+            -1.0, 8.0, -1.0,
+            -1.0, -1.0, -1.0,
+        ])
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    pub fn weights_mut(&mut self) -> &mut [f32] {
+        &mut self.weights
+    }
+
+    pub fn divisor(&self) -> f32 {
+        self.divisor
+    }
+
+    pub fn set_divisor(&mut self, divisor: f32) {
+        self.divisor = divisor;
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    /// `texel_size` is the size of one pixel of the sampled texture in texture coordinates.
+    pub fn to_uniform(&self, texel_size: [f32; 2]) -> ConvolutionUniform {
+        let mut weights = [[0.0; 4]; PACKED_WEIGHTS_LEN];
+        for (i, weight) in self.weights.iter().enumerate() {
+            weights[i / 4][i % 4] = *weight;
+        }
+        ConvolutionUniform {
+            weights,
+            texel_size,
+            size: self.size as u32,
+            divisor: self.divisor,
+            bias: self.bias,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// The built-in kernels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConvolutionPreset {
+    Identity,
+    Sharpen,
+    Emboss,
+    BoxBlur,
+    EdgeDetect,
+}
+
+impl ConvolutionPreset {
+    pub const ALL: [ConvolutionPreset; 5] = [
+        ConvolutionPreset::Identity,
+        ConvolutionPreset::Sharpen,
+        ConvolutionPreset::Emboss,
+        ConvolutionPreset::BoxBlur,
+        ConvolutionPreset::EdgeDetect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConvolutionPreset::Identity => "identity",
+            ConvolutionPreset::Sharpen => "sharpen",
+            ConvolutionPreset::Emboss => "emboss",
+            ConvolutionPreset::BoxBlur => "box_blur",
+            ConvolutionPreset::EdgeDetect => "edge_detect",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ConvolutionPreset::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    pub fn kernel(&self) -> ConvolutionKernel {
+        match self {
+            ConvolutionPreset::Identity => ConvolutionKernel::identity(),
+            ConvolutionPreset::Sharpen => ConvolutionKernel::sharpen(),
+            ConvolutionPreset::Emboss => ConvolutionKernel::emboss(),
+            ConvolutionPreset::BoxBlur => ConvolutionKernel::box_blur(5),
+            ConvolutionPreset::EdgeDetect => ConvolutionKernel::edge_detect(),
+        }
+    }
+}
@@ -1,3 +1,5 @@
 // The vertex type that we will use to represent a point on our triangle.
 pub mod model;
-pub mod pipeline;
\ No newline at end of file
+pub mod pipeline;
+pub mod offscreen;
+pub mod convolution;
\ No newline at end of file
@@ -1,26 +1,56 @@
 use nannou::wgpu;
 
+use crate::canvas::tiled::TileUniform;
+use crate::reflection::Bindings;
+use crate::shader_processing::convolution::{ConvolutionKernel, PACKED_WEIGHTS_LEN};
+use crate::uniforms::{ShaderUniform, UniformBuffer};
+
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vert {
     pub position: [f32; 2],
 }
 
 #[repr(C)]
 // This is so we can store this in a buffer
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
 pub struct ConvolutionUniform {
-    // We can't use cgmath with bytemuck directly, so we'll have
-    // to convert the Matrix4 into a 4x4 f32 array
-    pub convolution: [f32; 16],
+    // Row-major kernel weights, packed in groups of four to match `array<vec4<f32>, N>`
+    pub weights: [[f32; 4]; PACKED_WEIGHTS_LEN],
+    pub texel_size: [f32; 2],
+    pub size: u32,
+    pub divisor: f32,
+    pub bias: f32,
+    // Uniform structs are padded to a multiple of 16 bytes
+    pub _padding: [f32; 3],
 }
 
 pub struct ShaderModel {
-    pub bind_group: wgpu::BindGroup,
+    pub bindings: Bindings,
     pub render_pipeline: wgpu::RenderPipeline,
     pub vertex_buffer: wgpu::Buffer,
-    pub uniform_bind_group: wgpu::BindGroup,
-    pub convolution_uniform: ConvolutionUniform,
+    pub convolution_uniform: UniformBuffer<ConvolutionUniform>,
+    pub tile_uniform: UniformBuffer<TileUniform>,
+}
+
+impl ShaderModel {
+    /// Replaces the uniforms read by the fragment shader, starting from the next submitted frame.
+    pub fn set_uniforms(&mut self, queue: &wgpu::Queue, convolution_uniform: ConvolutionUniform) {
+        self.convolution_uniform.set(convolution_uniform);
+        self.convolution_uniform.flush(queue);
+    }
+
+    /// Renders only a part of the target from the next submitted frame, see `canvas::tiled`.
+    pub fn set_tile(&mut self, queue: &wgpu::Queue, tile: TileUniform) {
+        self.tile_uniform.set(tile);
+        self.tile_uniform.flush(queue);
+    }
+
+    /// Replaces the convolution kernel, keeping the texel size of the current texture.
+    pub fn set_convolution(&mut self, queue: &wgpu::Queue, convolution: &ConvolutionKernel) {
+        let texel_size = self.convolution_uniform.get().texel_size;
+        self.set_uniforms(queue, convolution.to_uniform(texel_size));
+    }
 }
 
 pub const QUAD: [Vert; 4] = [
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::mpsc;
+
+use nannou::image::{DynamicImage, ImageResult, RgbaImage};
+use nannou::wgpu;
+use nannou::wgpu::ShaderModuleDescriptor;
+
+use crate::shader_processing::convolution::ConvolutionKernel;
+use crate::shader_processing::model::ShaderModel;
+use crate::shader_processing::pipeline::{encode_render_pass, init_shader_for_target};
+
+/// Format of the textures rendered offscreen, 8 bits per channel so they can be read back
+/// directly into an `RgbaImage`.
+pub const OFFSCREEN_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug)]
+pub enum OffscreenError {
+    /// Neither a hardware nor a software (e.g. lavapipe, WARP) adapter is available.
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    BufferMap(wgpu::BufferAsyncError),
+}
+
+impl fmt::Display for OffscreenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OffscreenError::NoAdapter => write!(f, "no compatible GPU or software adapter found"),
+            OffscreenError::RequestDevice(err) => write!(f, "failed to request a device: {}", err),
+            OffscreenError::BufferMap(err) => write!(f, "failed to read back texture: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for OffscreenError {}
+
+/// A device and queue that are not tied to any window, for rendering in batch jobs, tests or on
+/// a build server without a display.
+pub struct HeadlessGpu {
+    pub adapter_info: wgpu::AdapterInfo,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl HeadlessGpu {
+    /// Requests a hardware adapter, falling back to a software one when there is none.
+    pub fn new() -> Result<Self, OffscreenError> {
+        futures::executor::block_on(Self::new_async())
+    }
+
+    pub async fn new_async() -> Result<Self, OffscreenError> {
+        let instance = wgpu::Instance::new(Default::default());
+        let mut options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::DEFAULT_POWER_PREFERENCE,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        };
+        let adapter = match instance.request_adapter(&options).await {
+            Some(adapter) => adapter,
+            None => {
+                options.force_fallback_adapter = true;
+                instance.request_adapter(&options).await.ok_or(OffscreenError::NoAdapter)?
+            }
+        };
+        let (device, queue) = adapter
+            .request_device(&wgpu::default_device_descriptor(), None)
+            .await
+            .map_err(OffscreenError::RequestDevice)?;
+        Ok(HeadlessGpu {
+            adapter_info: adapter.get_info(),
+            device,
+            queue,
+        })
+    }
+
+    /// Builds a `ShaderModel` whose pipeline targets `OFFSCREEN_TEXTURE_FORMAT`.
+    pub fn init_shader(&self, image: &DynamicImage, fs_desc: ShaderModuleDescriptor, convolution: &ConvolutionKernel) -> ShaderModel {
+        init_shader_for_target(
+            image,
+            &self.device,
+            &self.queue,
+            OFFSCREEN_TEXTURE_FORMAT,
+            1,
+            fs_desc,
+            convolution,
+        )
+    }
+
+    /// Renders the model into a new texture of the given size and reads it back.
+    pub fn render(&self, shader_model: &ShaderModel, size: [u32; 2]) -> Result<DynamicImage, OffscreenError> {
+        let target = create_render_target(&self.device, size);
+        let target_view = target.view().build();
+
+        let desc = wgpu::CommandEncoderDescriptor {
+            label: Some("offscreen-render"),
+        };
+        let mut encoder = self.device.create_command_encoder(&desc);
+        encode_render_pass(&mut encoder, &target_view, shader_model);
+        self.queue.submit(Some(encoder.finish()));
+
+        self.read_texture(&target).map(DynamicImage::ImageRgba8)
+    }
+
+    /// Renders the model and writes the result as a PNG file.
+    pub fn render_to_png<P: AsRef<Path>>(&self, shader_model: &ShaderModel, size: [u32; 2], path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let image = self.render(shader_model, size)?;
+        save_png(&image, path)?;
+        Ok(())
+    }
+
+    /// Copies a texture with 4 bytes per pixel (e.g. `Rgba8Unorm` or `Rgba8UnormSrgb`) back into
+    /// CPU memory, blocking until the GPU is done with it.
+    pub fn read_texture(&self, texture: &wgpu::Texture) -> Result<RgbaImage, OffscreenError> {
+        read_texture(&self.device, &self.queue, texture)
+    }
+}
+
+/// A texture of `OFFSCREEN_TEXTURE_FORMAT` that can be rendered to and read back.
+pub fn create_render_target(device: &wgpu::Device, size: [u32; 2]) -> wgpu::Texture {
+    wgpu::TextureBuilder::new()
+        .size(size)
+        .format(OFFSCREEN_TEXTURE_FORMAT)
+        .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC)
+        .build(device)
+}
+
+/// Copies a texture with 4 bytes per pixel back into CPU memory, blocking until the GPU is done.
+///
+/// The texture needs to have been created with `wgpu::TextureUsages::COPY_SRC`.
+pub fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<RgbaImage, OffscreenError> {
+    read_texture_region(device, queue, texture, [0, 0], texture.size())
+}
+
+/// `read_texture` for the rectangle of `size` pixels at `origin` only, which has to be inside of
+/// the texture.
+pub fn read_texture_region(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    origin: [u32; 2],
+    size: [u32; 2],
+) -> Result<RgbaImage, OffscreenError> {
+    let read = start_texture_read(device, queue, texture, origin, size);
+    device.poll(wgpu::Maintain::Wait);
+    read.try_finish().expect("the map callback is always called after polling")
+}
+
+/// Starts copying a region of a texture back into CPU memory without waiting for the GPU, see
+/// `PendingRead::try_finish`.
+pub fn start_texture_read(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    origin: [u32; 2],
+    size: [u32; 2],
+) -> PendingRead {
+    let [width, height] = size;
+    let unpadded_bytes_per_row = width * 4;
+    let bytes_per_row = unpadded_bytes_per_row + wgpu::compute_row_padding(unpadded_bytes_per_row);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("texture-readback"),
+        size: (bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let desc = wgpu::CommandEncoderDescriptor {
+        label: Some("texture-readback"),
+    };
+    let mut encoder = device.create_command_encoder(&desc);
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            origin: wgpu::Origin3d {
+                x: origin[0],
+                y: origin[1],
+                z: 0,
+            },
+            ..texture.as_image_copy()
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = mpsc::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).ok();
+    });
+    PendingRead {
+        buffer,
+        receiver,
+        size,
+        bytes_per_row,
+    }
+}
+
+/// A texture region being copied back by `start_texture_read`.
+pub struct PendingRead {
+    buffer: wgpu::Buffer,
+    receiver: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    size: [u32; 2],
+    bytes_per_row: u32,
+}
+
+impl PendingRead {
+    /// The pixels once the copy is done, `None` before and after. The device has to be polled
+    /// (e.g. with `wgpu::Maintain::Poll` every frame) for the copy to finish.
+    pub fn try_finish(&self) -> Option<Result<RgbaImage, OffscreenError>> {
+        if let Err(err) = self.receiver.try_recv().ok()? {
+            return Some(Err(OffscreenError::BufferMap(err)));
+        }
+
+        let [width, height] = self.size;
+        let unpadded_bytes_per_row = width as usize * 4;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * height as usize);
+        {
+            let padded = self.buffer.slice(..).get_mapped_range();
+            for row in padded.chunks(self.bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+        }
+        self.buffer.unmap();
+        Some(Ok(RgbaImage::from_raw(width, height, pixels).expect("buffer holds exactly width * height pixels")))
+    }
+}
+
+/// Converts the color channels of an image from linear to sRGB, in place.
+///
+/// Storage textures can't use an sRGB format, so compute kernels write linear values into
+/// `Rgba8Unorm` textures. Those need this conversion before being saved to look the same as they
+/// do when drawn to a window.
+pub fn encode_srgb(image: &mut RgbaImage) {
+    let lut: Vec<u8> = (0..=255u8)
+        .map(|value| {
+            let linear = value as f32 / 255.0;
+            let srgb = if linear <= 0.0031308 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            (srgb * 255.0).round() as u8
+        })
+        .collect();
+    for pixel in image.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = lut[*channel as usize];
+        }
+    }
+}
+
+pub fn save_png<P: AsRef<Path>>(image: &DynamicImage, path: P) -> ImageResult<()> {
+    image.save_with_format(path, nannou::image::ImageFormat::Png)
+}
@@ -1,83 +1,99 @@
 use std::cell::Ref;
 use nannou::{Frame, wgpu};
-use nannou::image::DynamicImage;
+use nannou::image::{DynamicImage, GenericImageView};
 use nannou::prelude::{BufferInitDescriptor, DeviceExt, Window};
 use nannou::wgpu::ShaderModuleDescriptor;
-use crate::shader_processing::model::{ConvolutionUniform, QUAD, ShaderModel, Vert};
+use crate::canvas::tiled::TileUniform;
+use crate::preprocessor::Preprocessor;
+use crate::shader_processing::convolution::ConvolutionKernel;
+use crate::shader_processing::model::{QUAD, ShaderModel, Vert};
+use crate::reflection::{BindResource, ShaderReflection};
+use crate::uniforms::UniformBuffer;
+
+/// A vertex stage that draws `QUAD` with its texture coordinates.
+pub const VERTEX_SHADER: &str = include_str!("shaders/vs.wgsl");
+
+/// `VERTEX_SHADER` with the texture coordinates of a `TileUniform`, bound to `tile` in group 3.
+pub fn tiled_vertex_shader() -> String {
+    match Preprocessor::new()
+        .with_uniform::<TileUniform>()
+        .process("tiled_vs.wgsl", include_str!("shaders/tiled_vs.wgsl"))
+    {
+        Ok(shader) => shader.source,
+        Err(err) => panic!("built-in shader failed to preprocess: {}", err),
+    }
+}
 
-pub fn init_shader(image: &DynamicImage, window: &Ref<Window>, fs_desc: ShaderModuleDescriptor, convolution: [f32; 16]) -> ShaderModel {
-    let device = window.device();
-    let format = Frame::TEXTURE_FORMAT;
-    let msaa_samples = window.msaa_samples();
+pub fn init_shader(image: &DynamicImage, window: &Ref<Window>, fs_desc: ShaderModuleDescriptor, convolution: &ConvolutionKernel) -> ShaderModel {
+    init_shader_for_target(
+        image,
+        window.device(),
+        window.queue(),
+        Frame::TEXTURE_FORMAT,
+        window.msaa_samples(),
+        fs_desc,
+        convolution,
+    )
+}
 
-    let vs_desc = wgpu::include_wgsl!("shaders/vs.wgsl");
+/// Same as `init_shader` but for any render target, not only a window's `Frame`.
+///
+/// The fragment shader must be WGSL, its bindings are found by name: the image is bound to `tex`,
+/// its sampler to `tex_sampler` and the `ConvolutionUniform` to `convolution`, each of them only if
+/// the shader declares it. Group 3 is taken by
+/// the `TileUniform` of the vertex stage, which can render the target in tiles.
+///
+/// `format` and `msaa_samples` must match the texture that will be passed to `encode_render_pass`.
+pub fn init_shader_for_target(
+    image: &DynamicImage,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    msaa_samples: u32,
+    fs_desc: ShaderModuleDescriptor,
+    convolution: &ConvolutionKernel,
+) -> ShaderModel {
+    let vs_source = tiled_vertex_shader();
+    let vs_desc = ShaderModuleDescriptor {
+        label: Some("tiled_vs.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(vs_source.as_str().into()),
+    };
 
+    let fs_source = match &fs_desc.source {
+        wgpu::ShaderSource::Wgsl(source) => source.to_string(),
+        _ => panic!("`init_shader` needs a WGSL fragment shader"),
+    };
     let vs_mod = device.create_shader_module(vs_desc);
     let fs_mod = device.create_shader_module(fs_desc);
 
     // Load the image as a texture.
-    let texture = wgpu::Texture::from_image(window, &image);
+    let texture = wgpu::Texture::from_image((device, queue), image);
     let texture_view = texture.view().build();
 
     // Create the sampler for sampling from the source texture.
-    let sampler_desc = wgpu::SamplerBuilder::new().into_descriptor();
-    let sampler_filtering = wgpu::sampler_filtering(&sampler_desc);
-    let sampler = device.create_sampler(&sampler_desc);
-
-    let bind_group_layout =
-        wgpu::BindGroupLayoutBuilder::new()
-            .texture(
-                wgpu::ShaderStages::FRAGMENT,
-                false,
-                wgpu::TextureViewDimension::D2,
-                texture_view.sample_type(),
-            )
-            .sampler(wgpu::ShaderStages::FRAGMENT, sampler_filtering)
-            .build(device);
-
-    let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }
-        ],
-        label: Some("uniform_bind_group_layout"),
+    let sampler = device.create_sampler(&wgpu::SamplerBuilder::new().into_descriptor());
+
+    let (width, height) = image.dimensions();
+    let convolution_uniform = convolution.to_uniform([1.0 / width as f32, 1.0 / height as f32]);
+
+    let convolution_uniform = UniformBuffer::new(device, "Convolution Matrix Buffer", convolution_uniform);
+    let tile_uniform = UniformBuffer::new(device, "Tile Buffer", TileUniform::FULL);
+
+    let bindings = ShaderReflection::bind_or_panic(&[&fs_source, &vs_source], "fragment shader", |reflection| {
+        let resources = [
+            ("tex", BindResource::TextureView(&texture_view)),
+            ("tex_sampler", BindResource::Sampler(&sampler)),
+            ("convolution", BindResource::Buffer(convolution_uniform.buffer())),
+            ("tile", BindResource::Buffer(tile_uniform.buffer())),
+        ];
+        // A fragment shader that e.g. doesn't convolve leaves some of them out
+        let resources: Vec<_> = resources
+            .into_iter()
+            .filter(|(name, _)| reflection.binding(name).is_some())
+            .collect();
+        reflection.bind(device, &resources)
     });
-
-    let convolution_uniform = ConvolutionUniform {
-        convolution
-    };
-
-    let convolution_uniform_buffer = device.create_buffer_init(
-        &wgpu::util::BufferInitDescriptor {
-            label: Some("Convolution Matrix Buffer"),
-            contents: bytemuck::cast_slice(&[convolution_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM,
-        }
-    );
-
-    let bind_group = wgpu::BindGroupBuilder::new()
-        .texture_view(&texture_view)
-        .sampler(&sampler)
-        .build(device, &bind_group_layout);
-
-    let uniform_bind_group = wgpu::BindGroupBuilder::new()
-        .binding(wgpu::BindingResource::Buffer(convolution_uniform_buffer.as_entire_buffer_binding()))
-        .build(device, &uniform_bind_group_layout);
-
-    let desc = wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[&bind_group_layout, &uniform_bind_group_layout],
-        push_constant_ranges: &[],
-    };
-    let pipeline_layout = device.create_pipeline_layout(&desc);
+    let pipeline_layout = bindings.pipeline_layout(device, "shader-model");
 
     let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vs_mod)
         .fragment_shader(&fs_mod)
@@ -87,7 +103,7 @@ pub fn init_shader(image: &DynamicImage, window: &Ref<Window>, fs_desc: ShaderMo
         .primitive_topology(wgpu::PrimitiveTopology::TriangleStrip)
         .build(device);
 
-    let vertices_bytes = vertices_as_bytes(&QUAD[..]);
+    let vertices_bytes = bytemuck::cast_slice(&QUAD[..]);
     let usage = wgpu::BufferUsages::VERTEX;
     let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
         label: None,
@@ -96,29 +112,30 @@ pub fn init_shader(image: &DynamicImage, window: &Ref<Window>, fs_desc: ShaderMo
     });
 
     ShaderModel {
-        bind_group,
-        uniform_bind_group,
+        bindings,
         vertex_buffer,
         render_pipeline,
         convolution_uniform,
+        tile_uniform,
     }
 }
 
 pub fn wgpu_render_pass(frame: Frame, shader_model: &ShaderModel) {
     let mut encoder = frame.command_encoder();
+    encode_render_pass(&mut encoder, frame.texture_view(), shader_model);
+}
+
+/// Records the draw of a `ShaderModel` into the given color attachment.
+pub fn encode_render_pass(encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureViewHandle, shader_model: &ShaderModel) {
     let mut render_pass = wgpu::RenderPassBuilder::new()
-        .color_attachment(frame.texture_view(), |color| color)
-        .begin(&mut encoder);
-    render_pass.set_bind_group(0, &shader_model.bind_group, &[]);
-    render_pass.set_bind_group(1, &shader_model.uniform_bind_group, &[]);
+        .color_attachment(target, |color| color)
+        .begin(encoder);
+    for (i, bind_group) in shader_model.bindings.groups().iter().enumerate() {
+        render_pass.set_bind_group(i as u32, bind_group, &[]);
+    }
     render_pass.set_pipeline(&shader_model.render_pipeline);
     render_pass.set_vertex_buffer(0, shader_model.vertex_buffer.slice(..));
     let vertex_range = 0..QUAD.len() as u32;
     let instance_range = 0..1;
     render_pass.draw(vertex_range, instance_range);
-}
-
-// See the `nannou::wgpu::bytes` documentation for why this is necessary.
-fn vertices_as_bytes(data: &[Vert]) -> &[u8] {
-    unsafe { wgpu::bytes::from_slice(data) }
 }
\ No newline at end of file
@@ -0,0 +1,18 @@
+// `vs.wgsl` with the texture coordinates of a part of the target only, see `canvas::tiled`.
+#include "uniforms/TileUniform.wgsl"
+
+@group(3) @binding(0)
+var<uniform> tile: TileUniform;
+
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @builtin(position) out_pos: vec4<f32>,
+};
+
+@vertex
+fn main(@location(0) pos: vec2<f32>) -> VertexOutput {
+    let target_coords = vec2<f32>(pos.x * 0.5 + 0.5, 1.0 - (pos.y * 0.5 + 0.5));
+    let tex_coords = tile.offset + target_coords * tile.scale;
+    let out_pos: vec4<f32> = vec4<f32>(pos, 0.0, 1.0);
+    return VertexOutput(tex_coords, out_pos);
+}
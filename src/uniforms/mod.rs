@@ -0,0 +1,472 @@
+use std::fmt;
+
+use nannou::prelude::DeviceExt;
+use nannou::wgpu;
+use nannou::wgpu::BufferInitDescriptor;
+
+pub use shader_uniform_derive::ShaderUniform;
+
+/// The WGSL types that can be used as fields of a uniform struct.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WgslType {
+    F32,
+    I32,
+    U32,
+    Vec2F32,
+    Vec3F32,
+    Vec4F32,
+    Vec2U32,
+    Vec4U32,
+    Mat4x4F32,
+    /// `array<element, len>`, uniform arrays must have a stride that is a multiple of 16.
+    Array(&'static WgslType, usize),
+}
+
+impl WgslType {
+    /// `AlignOf(T)` in the uniform address space.
+    pub const fn align(&self) -> usize {
+        match self {
+            WgslType::F32 | WgslType::I32 | WgslType::U32 => 4,
+            WgslType::Vec2F32 | WgslType::Vec2U32 => 8,
+            WgslType::Vec3F32 | WgslType::Vec4F32 | WgslType::Vec4U32 | WgslType::Mat4x4F32 => 16,
+            WgslType::Array(element, _) => round_up(16, element.align()),
+        }
+    }
+
+    /// `SizeOf(T)`, arrays include the padding between elements.
+    pub const fn size(&self) -> usize {
+        match self {
+            WgslType::F32 | WgslType::I32 | WgslType::U32 => 4,
+            WgslType::Vec2F32 | WgslType::Vec2U32 => 8,
+            WgslType::Vec3F32 => 12,
+            WgslType::Vec4F32 | WgslType::Vec4U32 => 16,
+            WgslType::Mat4x4F32 => 64,
+            WgslType::Array(element, len) => array_stride(element) * *len,
+        }
+    }
+
+    /// The type as written in WGSL source.
+    pub fn name(&self) -> String {
+        match self {
+            WgslType::F32 => "f32".to_string(),
+            WgslType::I32 => "i32".to_string(),
+            WgslType::U32 => "u32".to_string(),
+            WgslType::Vec2F32 => "vec2<f32>".to_string(),
+            WgslType::Vec3F32 => "vec3<f32>".to_string(),
+            WgslType::Vec4F32 => "vec4<f32>".to_string(),
+            WgslType::Vec2U32 => "vec2<u32>".to_string(),
+            WgslType::Vec4U32 => "vec4<u32>".to_string(),
+            WgslType::Mat4x4F32 => "mat4x4<f32>".to_string(),
+            WgslType::Array(element, len) => format!("array<{}, {}>", element.name(), len),
+        }
+    }
+}
+
+/// A field of a uniform struct as seen from WGSL, with the offset of the matching Rust field.
+///
+/// Rust only padding fields (like `_padding: [f32; 3]`) are not listed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UniformField {
+    pub name: &'static str,
+    pub offset: usize,
+    pub ty: WgslType,
+}
+
+impl UniformField {
+    pub const fn new(name: &'static str, offset: usize, ty: WgslType) -> Self {
+        UniformField { name, offset, ty }
+    }
+}
+
+/// A Rust struct that mirrors a WGSL struct used as `var<uniform>`.
+///
+/// Usually derived, which also checks the layout at compile time:
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderUniform)]
+/// pub struct Uniforms {
+///     time: f32,
+///     accentuate: f32,
+/// }
+/// ```
+///
+/// The WGSL side can then come from `wgsl_struct`, e.g. with `Preprocessor::with_uniform`,
+/// instead of being written by hand.
+pub trait WgslUniform: bytemuck::Pod {
+    const FIELDS: &'static [UniformField];
+
+    /// Name of the WGSL struct, the name of the Rust type by default.
+    fn wgsl_name() -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// The WGSL declaration of the struct.
+    fn wgsl_struct() -> String {
+        wgsl_struct(Self::wgsl_name(), Self::FIELDS)
+    }
+}
+
+/// Declares a WGSL struct with the given fields.
+pub fn wgsl_struct(name: &str, fields: &[UniformField]) -> String {
+    let mut wgsl = format!("struct {} {{\n", name);
+    for field in fields {
+        wgsl.push_str(&format!("    {}: {},\n", field.name, field.ty.name()));
+    }
+    wgsl.push_str("};\n");
+    wgsl
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The Rust field is not where WGSL expects it, explicit padding is missing or is too big.
+    FieldOffset {
+        field: &'static str,
+        rust_offset: usize,
+        wgsl_offset: usize,
+    },
+    /// Arrays in uniform buffers need a stride that is a multiple of 16, e.g. `array<vec4<f32>, N>`
+    /// instead of `array<f32, N>`.
+    ArrayStride {
+        field: &'static str,
+        stride: usize,
+    },
+    /// The struct needs trailing padding (or has too much of it).
+    StructSize {
+        rust_size: usize,
+        wgsl_size: usize,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::FieldOffset { field, rust_offset, wgsl_offset } => write!(
+                f,
+                "field `{}` is at offset {} in Rust but WGSL expects it at {}",
+                field, rust_offset, wgsl_offset
+            ),
+            LayoutError::ArrayStride { field, stride } => write!(
+                f,
+                "array field `{}` has a stride of {} bytes, uniform arrays need a multiple of 16",
+                field, stride
+            ),
+            LayoutError::StructSize { rust_size, wgsl_size } => write!(
+                f,
+                "struct is {} bytes in Rust but {} bytes in WGSL, check the trailing padding",
+                rust_size, wgsl_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Checks that `T` has the memory layout WGSL uses for its `FIELDS` in the uniform address space.
+pub fn check_layout<T: WgslUniform>() -> Result<(), LayoutError> {
+    for (i, field) in T::FIELDS.iter().enumerate() {
+        if let WgslType::Array(element, _) = field.ty {
+            if !has_uniform_stride(&field.ty) {
+                return Err(LayoutError::ArrayStride {
+                    field: field.name,
+                    stride: array_stride(element),
+                });
+            }
+        }
+        let wgsl_offset = wgsl_offset(T::FIELDS, i);
+        if field.offset != wgsl_offset {
+            return Err(LayoutError::FieldOffset {
+                field: field.name,
+                rust_offset: field.offset,
+                wgsl_offset,
+            });
+        }
+    }
+
+    let wgsl_size = wgsl_size(T::FIELDS);
+    let rust_size = std::mem::size_of::<T>();
+    if rust_size != wgsl_size {
+        return Err(LayoutError::StructSize { rust_size, wgsl_size });
+    }
+    Ok(())
+}
+
+/// Offset of `fields[index]` in a WGSL struct with these fields.
+pub const fn wgsl_offset(fields: &[UniformField], index: usize) -> usize {
+    let mut end = 0;
+    let mut i = 0;
+    while i < index {
+        end = round_up(fields[i].ty.align(), end) + fields[i].ty.size();
+        i += 1;
+    }
+    round_up(fields[index].ty.align(), end)
+}
+
+/// Size of a WGSL struct with these fields, including its trailing padding.
+pub const fn wgsl_size(fields: &[UniformField]) -> usize {
+    let mut end = 0;
+    let mut align = 4;
+    let mut i = 0;
+    while i < fields.len() {
+        let ty = &fields[i].ty;
+        end = round_up(ty.align(), end) + ty.size();
+        if ty.align() > align {
+            align = ty.align();
+        }
+        i += 1;
+    }
+    round_up(align, end)
+}
+
+/// Whether the type can be used in a uniform buffer, arrays need a stride that is a multiple of
+/// 16.
+pub const fn has_uniform_stride(ty: &WgslType) -> bool {
+    match ty {
+        WgslType::Array(element, _) => array_stride(element).is_multiple_of(16),
+        _ => true,
+    }
+}
+
+pub const fn round_up(align: usize, value: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+const fn array_stride(element: &WgslType) -> usize {
+    round_up(element.align(), element.size())
+}
+
+/// A uniform buffer that keeps a CPU copy of its value and only uploads it when it changes.
+pub struct UniformBuffer<T: WgslUniform> {
+    buffer: wgpu::Buffer,
+    value: T,
+    dirty: bool,
+}
+
+impl<T: WgslUniform> UniformBuffer<T> {
+    /// Panics if the layout of `T` doesn't follow the WGSL rules, see `check_layout`.
+    pub fn new(device: &wgpu::Device, label: &str, value: T) -> Self {
+        if let Err(err) = check_layout::<T>() {
+            panic!("`{}` can't be used as a uniform: {}", std::any::type_name::<T>(), err);
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::bytes_of(&value),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        UniformBuffer {
+            buffer,
+            value,
+            dirty: false,
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Marks the buffer as dirty only if the value actually changed.
+    pub fn set(&mut self, value: T) {
+        if bytemuck::bytes_of(&value) != bytemuck::bytes_of(&self.value) {
+            self.value = value;
+            self.dirty = true;
+        }
+    }
+
+    pub fn update<F: FnOnce(&mut T)>(&mut self, f: F) {
+        let mut value = self.value;
+        f(&mut value);
+        self.set(value);
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Uploads the value if it changed since the last flush, returns whether it did.
+    ///
+    /// The write happens at the start of the next `queue.submit`.
+    pub fn flush(&mut self, queue: &wgpu::Queue) -> bool {
+        if !self.dirty {
+            return false;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.value));
+        self.dirty = false;
+        true
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn field(ty: WgslType) -> UniformField {
+        UniformField::new("field", 0, ty)
+    }
+
+    #[test]
+    fn packs_scalars_after_vec3() {
+        let fields = [field(WgslType::Vec3F32), field(WgslType::F32), field(WgslType::U32)];
+        assert_eq!(wgsl_offset(&fields, 0), 0);
+        assert_eq!(wgsl_offset(&fields, 1), 12);
+        assert_eq!(wgsl_offset(&fields, 2), 16);
+        assert_eq!(wgsl_size(&fields), 32);
+    }
+
+    #[test]
+    fn aligns_vectors_after_scalars() {
+        let fields = [field(WgslType::F32), field(WgslType::Vec3F32), field(WgslType::Vec2F32)];
+        assert_eq!(wgsl_offset(&fields, 1), 16);
+        assert_eq!(wgsl_offset(&fields, 2), 32);
+        assert_eq!(wgsl_size(&fields), 48);
+    }
+
+    #[test]
+    fn rounds_array_strides_up_to_the_element_alignment() {
+        static VEC3: WgslType = WgslType::Vec3F32;
+        static F32: WgslType = WgslType::F32;
+        static VEC4: WgslType = WgslType::Vec4F32;
+        assert_eq!(WgslType::Array(&VEC3, 3).size(), 48);
+        assert_eq!(WgslType::Array(&VEC3, 3).align(), 16);
+        assert!(has_uniform_stride(&WgslType::Array(&VEC3, 3)));
+        assert!(has_uniform_stride(&WgslType::Array(&VEC4, 2)));
+        assert!(!has_uniform_stride(&WgslType::Array(&F32, 4)));
+
+        // Arrays are aligned to 16 even when their elements are not
+        let fields = [field(WgslType::F32), field(WgslType::Array(&F32, 4))];
+        assert_eq!(wgsl_offset(&fields, 1), 16);
+    }
+
+    #[test]
+    fn matches_the_layout_of_naga() {
+        static VEC4: WgslType = WgslType::Vec4F32;
+        let fields = [
+            UniformField::new("a", 0, WgslType::F32),
+            UniformField::new("b", 16, WgslType::Vec3F32),
+            UniformField::new("c", 28, WgslType::U32),
+            UniformField::new("d", 32, WgslType::Vec2F32),
+            UniformField::new("e", 48, WgslType::Array(&VEC4, 2)),
+            UniformField::new("f", 80, WgslType::F32),
+        ];
+        let wgsl = format!(
+            "{}@group(0) @binding(0) var<uniform> uniforms: Uniforms;",
+            wgsl_struct("Uniforms", &fields)
+        );
+        let module = naga::front::wgsl::parse_str(&wgsl).unwrap();
+        let (_, ty) = module.types.iter().find(|(_, ty)| ty.name.as_deref() == Some("Uniforms")).unwrap();
+        let naga::TypeInner::Struct { members, span } = &ty.inner else {
+            panic!("`Uniforms` is not a struct");
+        };
+        let offsets: Vec<_> = members.iter().map(|member| member.offset as usize).collect();
+        let expected: Vec<_> = (0..fields.len()).map(|i| wgsl_offset(&fields, i)).collect();
+        assert_eq!(offsets, expected);
+        assert_eq!(*span as usize, wgsl_size(&fields));
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Unpadded {
+        a: f32,
+        b: [f32; 3],
+    }
+
+    /// The layout the derive would reject at compile time: `b` is a `vec3` at offset 4.
+    impl WgslUniform for Unpadded {
+        const FIELDS: &'static [UniformField] = &[
+            UniformField::new("a", 0, WgslType::F32),
+            UniformField::new("b", 4, WgslType::Vec3F32),
+        ];
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct MissingTrailingPadding {
+        a: [f32; 3],
+        b: f32,
+        c: f32,
+    }
+
+    impl WgslUniform for MissingTrailingPadding {
+        const FIELDS: &'static [UniformField] = &[
+            UniformField::new("a", 0, WgslType::Vec3F32),
+            UniformField::new("b", 12, WgslType::F32),
+            UniformField::new("c", 16, WgslType::F32),
+        ];
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct ScalarArray {
+        weights: [f32; 4],
+    }
+
+    impl WgslUniform for ScalarArray {
+        const FIELDS: &'static [UniformField] = &[UniformField::new("weights", 0, WgslType::Array(&WgslType::F32, 4))];
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Padded {
+        a: f32,
+        _padding: [f32; 3],
+        b: [f32; 3],
+        c: f32,
+    }
+
+    impl WgslUniform for Padded {
+        const FIELDS: &'static [UniformField] = &[
+            UniformField::new("a", 0, WgslType::F32),
+            UniformField::new("b", 16, WgslType::Vec3F32),
+            UniformField::new("c", 28, WgslType::F32),
+        ];
+    }
+
+    #[test]
+    fn rejects_misplaced_fields() {
+        assert_eq!(
+            check_layout::<Unpadded>(),
+            Err(LayoutError::FieldOffset {
+                field: "b",
+                rust_offset: 4,
+                wgsl_offset: 16
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_missing_trailing_padding() {
+        assert_eq!(
+            check_layout::<MissingTrailingPadding>(),
+            Err(LayoutError::StructSize {
+                rust_size: 20,
+                wgsl_size: 32
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_arrays_of_scalars() {
+        assert_eq!(
+            check_layout::<ScalarArray>(),
+            Err(LayoutError::ArrayStride {
+                field: "weights",
+                stride: 4
+            })
+        );
+    }
+
+    #[test]
+    fn accepts_explicit_padding() {
+        assert_eq!(check_layout::<Padded>(), Ok(()));
+        assert_eq!(Padded::wgsl_name(), "Padded");
+        assert_eq!(Padded::wgsl_struct(), "struct Padded {\n    a: f32,\n    b: vec3<f32>,\n    c: f32,\n};\n");
+    }
+}
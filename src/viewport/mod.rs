@@ -0,0 +1,294 @@
+use nannou::geom::{pt2, vec2, Rect};
+use nannou::window::Window;
+use nannou::winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use nannou_egui::egui;
+
+const MIN_ZOOM: f32 = 1.0 / 32.0;
+const MAX_ZOOM: f32 = 64.0;
+/// Zoom of one notch of a mouse wheel.
+const WHEEL_ZOOM: f32 = 1.1;
+/// Pixels scrolled on a touchpad for the zoom of one notch.
+const PIXELS_PER_NOTCH: f32 = 50.0;
+
+/// How an image is scaled to the window before zooming.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    /// As large as it fits, with bars on two sides if the aspect ratios differ.
+    #[default]
+    Fit,
+    /// Covers the whole window, cropping two sides if the aspect ratios differ.
+    Fill,
+    /// Covers the whole window, distorted if the aspect ratios differ.
+    Stretch,
+    /// One pixel of the image per physical pixel of the window.
+    Actual,
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 4] = [ScaleMode::Fit, ScaleMode::Fill, ScaleMode::Stretch, ScaleMode::Actual];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleMode::Fit => "Fit",
+            ScaleMode::Fill => "Fill",
+            ScaleMode::Stretch => "Stretch",
+            ScaleMode::Actual => "1:1",
+        }
+    }
+}
+
+/// Where an image is shown in a window: placed by a `ScaleMode`, then zoomed with the mouse wheel
+/// around the cursor and panned by dragging.
+///
+/// Window coordinates are physical pixels from the top left corner, like the cursor positions of
+/// winit and the size of a `Frame` texture. Image coordinates are pixels from the top left corner
+/// of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    pub mode: ScaleMode,
+    zoom: f32,
+    /// Offset of the center of the image from the center of the window.
+    pan: [f32; 2],
+    cursor: Option<[f32; 2]>,
+    dragging: bool,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::new(ScaleMode::default())
+    }
+}
+
+impl Viewport {
+    pub fn new(mode: ScaleMode) -> Self {
+        Viewport {
+            mode,
+            zoom: 1.0,
+            pan: [0.0, 0.0],
+            cursor: None,
+            dragging: false,
+        }
+    }
+
+    /// Multiplies the scale of the mode.
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Back to the image as placed by the mode.
+    pub fn reset(&mut self) {
+        self.zoom = 1.0;
+        self.pan = [0.0, 0.0];
+    }
+
+    /// The last position of the cursor in the window, `None` once it left.
+    pub fn cursor(&self) -> Option<[f32; 2]> {
+        self.cursor
+    }
+
+    /// Where the image is drawn, as `[x, y, width, height]` in window coordinates. Parts of it
+    /// can be outside of the window.
+    pub fn rect(&self, image_size: [u32; 2], window_size: [f32; 2]) -> [f32; 4] {
+        let [width, height] = image_size.map(|side| side as f32);
+        let [window_width, window_height] = window_size;
+        let (scale_x, scale_y) = match self.mode {
+            ScaleMode::Fit => {
+                let scale = (window_width / width).min(window_height / height);
+                (scale, scale)
+            }
+            ScaleMode::Fill => {
+                let scale = (window_width / width).max(window_height / height);
+                (scale, scale)
+            }
+            ScaleMode::Stretch => (window_width / width, window_height / height),
+            ScaleMode::Actual => (1.0, 1.0),
+        };
+        let (scaled_width, scaled_height) = (width * scale_x * self.zoom, height * scale_y * self.zoom);
+        [
+            (window_width - scaled_width) / 2.0 + self.pan[0],
+            (window_height - scaled_height) / 2.0 + self.pan[1],
+            scaled_width,
+            scaled_height,
+        ]
+    }
+
+    /// `rect` in the coordinates of a nannou `Draw` of `window`: points from its center, with y up.
+    pub fn draw_rect(&self, image_size: [u32; 2], window: &Window) -> Rect {
+        let (window_width, window_height) = window.inner_size_pixels();
+        let [x, y, width, height] = self.rect(image_size, [window_width as f32, window_height as f32]);
+        let center = pt2(
+            x + width / 2.0 - window_width as f32 / 2.0,
+            window_height as f32 / 2.0 - (y + height / 2.0),
+        );
+        let scale_factor = window.scale_factor();
+        Rect::from_xy_wh(center / scale_factor, vec2(width, height) / scale_factor)
+    }
+
+    /// The image coordinates under a point of the window, outside of the image if the point is.
+    pub fn window_to_image(&self, point: [f32; 2], image_size: [u32; 2], window_size: [f32; 2]) -> [f32; 2] {
+        let [x, y, width, height] = self.rect(image_size, window_size);
+        [
+            (point[0] - x) / width * image_size[0] as f32,
+            (point[1] - y) / height * image_size[1] as f32,
+        ]
+    }
+
+    pub fn image_to_window(&self, point: [f32; 2], image_size: [u32; 2], window_size: [f32; 2]) -> [f32; 2] {
+        let [x, y, width, height] = self.rect(image_size, window_size);
+        [
+            x + point[0] / image_size[0] as f32 * width,
+            y + point[1] / image_size[1] as f32 * height,
+        ]
+    }
+
+    /// Multiplies the zoom, keeping the part of the image under `point` in place.
+    pub fn zoom_at(&mut self, factor: f32, point: [f32; 2], image_size: [u32; 2], window_size: [f32; 2]) {
+        let anchor = self.window_to_image(point, image_size, window_size);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let moved = self.image_to_window(anchor, image_size, window_size);
+        self.pan[0] += point[0] - moved[0];
+        self.pan[1] += point[1] - moved[1];
+    }
+
+    /// Zooms with the mouse wheel and pans while the left button is held. Returns whether the
+    /// view changed.
+    ///
+    /// `captured` is for when something drawn over the image, like egui, uses the pointer: the
+    /// cursor is still followed and a drag can end, but none starts and the wheel is ignored.
+    pub fn handle_event(&mut self, event: &WindowEvent, image_size: [u32; 2], window_size: [f32; 2], captured: bool) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = [position.x as f32, position.y as f32];
+                let previous = self.cursor.replace(position);
+                match previous {
+                    Some(previous) if self.dragging => {
+                        self.pan[0] += position[0] - previous[0];
+                        self.pan[1] += position[1] - previous[1];
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                false
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.dragging = *state == ElementState::Pressed && !captured;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } if !captured => {
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_NOTCH,
+                };
+                let center = window_size.map(|side| side / 2.0);
+                let point = self.cursor.unwrap_or(center);
+                self.zoom_at(WHEEL_ZOOM.powf(notches), point, image_size, window_size);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Shows a button per mode, the zoom and a button to reset it. Returns whether the view
+    /// changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            for mode in ScaleMode::ALL {
+                changed |= ui.selectable_value(&mut self.mode, mode, mode.name()).changed();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(format!("Zoom: {:.0}%", self.zoom * 100.0));
+            if ui.button("Reset").clicked() {
+                self.reset();
+                changed = true;
+            }
+        });
+        changed
+    }
+}
+
+/// Whether egui uses the pointer, e.g. to drag a slider, for the `captured` of
+/// `Viewport::handle_event`.
+pub fn egui_captures_pointer(ctx: &egui::Context) -> bool {
+    ctx.is_pointer_over_area() || ctx.is_using_pointer()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE: [u32; 2] = [400, 200];
+    const LANDSCAPE: [f32; 2] = [1000.0, 400.0];
+    const PORTRAIT: [f32; 2] = [300.0, 900.0];
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        let close = actual.iter().zip(expected).all(|(actual, expected)| (actual - expected).abs() < 1e-3);
+        assert!(close, "{:?} is not close to {:?}", actual, expected);
+    }
+
+    #[test]
+    fn fits_the_image_with_bars() {
+        let viewport = Viewport::new(ScaleMode::Fit);
+        // Limited by the height, bars left and right
+        assert_eq!(viewport.rect(IMAGE, LANDSCAPE), [100.0, 0.0, 800.0, 400.0]);
+        // Limited by the width, bars above and below
+        assert_eq!(viewport.rect(IMAGE, PORTRAIT), [0.0, 375.0, 300.0, 150.0]);
+    }
+
+    #[test]
+    fn fills_the_window_by_cropping() {
+        let viewport = Viewport::new(ScaleMode::Fill);
+        assert_eq!(viewport.rect(IMAGE, LANDSCAPE), [0.0, -50.0, 1000.0, 500.0]);
+        assert_eq!(viewport.rect(IMAGE, PORTRAIT), [-750.0, 0.0, 1800.0, 900.0]);
+    }
+
+    #[test]
+    fn stretches_or_keeps_the_pixels() {
+        assert_eq!(Viewport::new(ScaleMode::Stretch).rect(IMAGE, PORTRAIT), [0.0, 0.0, 300.0, 900.0]);
+        assert_eq!(Viewport::new(ScaleMode::Actual).rect(IMAGE, PORTRAIT), [-50.0, 350.0, 400.0, 200.0]);
+    }
+
+    #[test]
+    fn maps_window_points_to_the_image_and_back() {
+        for mode in ScaleMode::ALL {
+            let mut viewport = Viewport::new(mode);
+            viewport.zoom_at(1.7, [123.0, 45.0], IMAGE, PORTRAIT);
+            for point in [[0.0, 0.0], [150.0, 450.0], [299.0, 12.5], [-40.0, 1000.0]] {
+                let image_point = viewport.window_to_image(point, IMAGE, PORTRAIT);
+                assert_close(viewport.image_to_window(image_point, IMAGE, PORTRAIT), point);
+            }
+        }
+        // The corners of the image are those of its rect
+        let viewport = Viewport::new(ScaleMode::Fit);
+        assert_close(viewport.window_to_image([100.0, 0.0], IMAGE, LANDSCAPE), [0.0, 0.0]);
+        assert_close(viewport.window_to_image([900.0, 400.0], IMAGE, LANDSCAPE), [400.0, 200.0]);
+    }
+
+    #[test]
+    fn zooms_around_the_anchor() {
+        let mut viewport = Viewport::new(ScaleMode::Fit);
+        let point = [250.0, 100.0];
+        let anchor = viewport.window_to_image(point, IMAGE, LANDSCAPE);
+        viewport.zoom_at(3.0, point, IMAGE, LANDSCAPE);
+        assert_eq!(viewport.zoom(), 3.0);
+        assert_close(viewport.image_to_window(anchor, IMAGE, LANDSCAPE), point);
+
+        // Also when the zoom is clamped
+        let point = [700.0, 380.0];
+        let anchor = viewport.window_to_image(point, IMAGE, LANDSCAPE);
+        viewport.zoom_at(1000.0, point, IMAGE, LANDSCAPE);
+        assert_eq!(viewport.zoom(), MAX_ZOOM);
+        assert_close(viewport.image_to_window(anchor, IMAGE, LANDSCAPE), point);
+
+        viewport.reset();
+        assert_eq!(viewport.rect(IMAGE, LANDSCAPE), [100.0, 0.0, 800.0, 400.0]);
+    }
+}